@@ -1,18 +1,235 @@
+use gloo::timers::callback::Timeout;
+use gloo::worker::WorkerBridge;
 use yew::prelude::*;
 use crate::game;
+use crate::game::board::animate;
 
-
+/// how long the opponent gets to respond to a spell before it resolves on its own
+const REACTION_WINDOW_MS: u32 = 5_000;
 
 #[function_component(App)]
 pub fn app() -> Html {
-    let g = use_state(|| game::Game::new());
+    let linked = use_mut_ref(game::share::from_location);
+    let g = use_state(|| linked.borrow().clone().unwrap_or_default());
+    let selected = use_state(|| None::<chess::Square>);
+    let drag = use_state(|| None::<game::touch::Drag>);
+    let viewing = use_state(|| None::<usize>);
+    let detail = use_state(|| None::<game::Detail>);
+    let focus = use_state(game::keys::Focus::default);
+    let theme = use_state(game::theme::Theme::default);
+    let themes = use_state(Vec::new);
+    // a linked game skips the draft and the offer to resume
+    let draft = use_state(|| linked.borrow().is_none().then(|| game::draft::Draft::new(js_sys::Date::now() as u64)));
+    let saved = use_state(|| linked.borrow().is_none().then(game::save::load).flatten());
+    let computer = use_state(|| None::<game::ai::Computer>);
+    // the finished game taken apart, and the position it shows
+    let analysis = use_state(|| None::<game::analysis::Analysis>);
+    let step = use_state(|| 0usize);
+    // the analysis as of this render, for reviews arriving later
+    let analysed = use_mut_ref(|| (*analysis).clone());
+    *analysed.borrow_mut() = (*analysis).clone();
+    let reviewer = use_mut_ref(|| None::<WorkerBridge<game::ai::Opponent>>);
+    // the last hint, shown until anything happens on the board
+    let hint = use_state(|| None::<game::ai::HintState>);
+    let opponent = use_mut_ref(|| None::<WorkerBridge<game::ai::Opponent>>);
+    // a worker of its own, so a hint never gets the computer's move
+    let helper = use_mut_ref(|| None::<WorkerBridge<game::ai::Opponent>>);
+    // the game as of this render, for the opponent's answers arriving later
+    let latest = use_mut_ref(|| (*g).clone());
+    *latest.borrow_mut() = (*g).clone();
+    let prev = use_mut_ref(|| (*g).clone());
+    let running = use_mut_ref(|| (0.0, Vec::new()));
+
+    {
+        let state = g.clone();
+        // resolves whatever the game is by then, anything else may have changed during the window
+        let latest = latest.clone();
+        use_effect_with_deps(move |pending| {
+            let timeout = pending.then(|| Timeout::new(REACTION_WINDOW_MS, move || state.set(latest.borrow().resolve())));
+            move || drop(timeout)
+        }, g.pending().is_some());
+    }
+
+    {
+        let state = g.clone();
+        let searching = matches!(*hint, Some(game::ai::HintState::Searching));
+        let hint = hint.clone();
+        let latest = latest.clone();
+        use_effect_with_deps(move |searching| {
+            if *searching {
+                let think = game::ai::ask_hint(&latest.borrow());
+                let mut helper = helper.borrow_mut();
+                let bridge = helper.get_or_insert_with(|| game::ai::spawn(move |thought| {
+                    // a hint for a position that changed in the meantime is dropped and not counted
+                    let current = latest.borrow().clone();
+                    if current.as_seen_by(current.acting_side()).to_fen() != thought.fen {
+                        return hint.set(None);
+                    }
+                    match current.hint_from(|_| thought.action) {
+                        Ok((game, found)) => {
+                            hint.set(Some(game::ai::HintState::Found(found)));
+                            state.set(game);
+                        },
+                        Err(e) => {
+                            log::info!("{}", e);
+                            hint.set(None);
+                        },
+                    }
+                }));
+                bridge.send(think);
+            }
+            || ()
+        }, searching);
+    }
+
+    {
+        let state = g.clone();
+        // only once the game is under way, not behind the new game or resume screen
+        let playing = draft.is_none() && saved.is_none();
+        use_effect_with_deps(move |(current, computer, playing)| {
+            if let Some(computer) = computer.filter(|computer| *playing && computer.side == current.acting_side()) {
+                let mut opponent = opponent.borrow_mut();
+                let bridge = opponent.get_or_insert_with(|| game::ai::spawn(move |thought| {
+                    // answers to positions that changed in the meantime are dropped
+                    let current = latest.borrow().clone();
+                    if current.as_seen_by(current.acting_side()).to_fen() != thought.fen {
+                        return;
+                    }
+                    match thought.action.and_then(|action| current.apply(&action)) {
+                        Some(next) => state.set(next),
+                        None => log::info!("the computer has nothing to do"),
+                    }
+                }));
+                bridge.send(game::ai::think(current, &computer, js_sys::Date::now() as u64));
+            }
+            || ()
+        }, ((*g).clone(), *computer, playing));
+    }
+
+    {
+        // one position at a time, each reply asks for the next
+        let state = analysis.clone();
+        let analysed = analysed.clone();
+        use_effect_with_deps(move |next| {
+            if let Some(fen) = next {
+                let mut reviewer = reviewer.borrow_mut();
+                let bridge = reviewer.get_or_insert_with(|| game::ai::spawn(move |thought| {
+                    let Some(score) = thought.score else { return };
+                    let mut current = analysed.borrow().clone();
+                    let review = game::analysis::Review { best: thought.action, score };
+                    // reviews for an analysis closed or started over in the meantime are dropped
+                    if current.as_mut().is_some_and(|analysis| analysis.add_review(&thought.fen, review)) {
+                        state.set(current);
+                    }
+                }));
+                bridge.send(game::ai::Think { fen: fen.clone(), purpose: game::ai::Purpose::Review });
+            }
+            || ()
+        }, analysis.as_ref().and_then(game::analysis::Analysis::next));
+    }
+
+    {
+        let theme = theme.clone();
+        let themes = themes.clone();
+        use_effect_with_deps(move |_| {
+            game::theme::list(themes);
+            if let Some(id) = game::theme::saved() {
+                game::theme::load(id, theme);
+            }
+        }, ());
+    }
+
+    {
+        // nothing is written until a game is actually under way, so the last save survives the prompt
+        let playing = draft.is_none() && saved.is_none();
+        use_effect_with_deps(move |(current, playing)| {
+            if *playing {
+                game::save::store(current);
+            }
+            || ()
+        }, ((*g).clone(), playing));
+    }
+
+    if saved.is_some() {
+        return html! {
+            <div class="app" style={theme.style()}>
+                {game::save::render(&saved, &g, &draft)}
+            </div>
+        };
+    }
+
+    if draft.is_some() {
+        return html! {
+            <div class="app" style={theme.style()}>
+                {game::draft::render(&draft, &g, &computer)}
+            </div>
+        };
+    }
+
+    if analysis.is_some() {
+        return html! {
+            <div class="app" style={theme.style()}>
+                {game::analysis::render(&g, &analysis, &step, &theme)}
+            </div>
+        };
+    }
+
+    let animations = {
+        // diffed right here during render so the first frame of a change is already animated
+        let now = js_sys::Date::now();
+        let mut found = animate::diff(prev.borrow().board(), g.board(), &g.view());
+        found.extend(animate::effects(&prev.borrow(), &g));
+        *prev.borrow_mut() = (*g).clone();
+        if !found.is_empty() {
+            *running.borrow_mut() = (now, found);
+        }
+        let (started, animations) = running.borrow().clone();
+        match now - started < animate::ANIMATION_MS {
+            true => animations,
+            false => Vec::new(),
+        }
+    };
 
-    let board = game::board::render(g.clone());
-    let menu = game::menu::render(&g);
+    let suggestion = match &*hint {
+        Some(game::ai::HintState::Found(found)) => Some(found),
+        _ => None,
+    };
+    // the board takes no input while the computer thinks
+    let thinking = computer.is_some_and(|computer| computer.side == g.acting_side());
+    let viewer = g.viewer(*computer);
+    let board = match viewing.and_then(|i| g.history().get(i)) {
+        Some(entry) => game::board::render_past(&g, &entry.board, &theme),
+        None => game::board::render(g.clone(), selected, (!thinking).then_some(&drag), &detail, &focus, game::board::Looks { theme: &theme, suggestion, animations: &animations }, viewer),
+    };
+    let history = game::history::render(&g, &viewing, &analysis, &step, viewer);
+    let menu = game::menu::render(&g, &drag, &detail, &focus, &computer, &draft, &hint);
+    // everything that happened on the latest ply, read out by screen readers
+    let announcement = match &focus.aiming {
+        Some(spell) => format!("Choose a square to cast {} on, escape to cancel", spell.name()),
+        None => {
+            let last = g.history().last().map(|entry| entry.ply);
+            g.history().iter()
+                .filter(|entry| Some(entry.ply) == last)
+                .map(|entry| entry.announce(viewer))
+                .collect::<Vec<_>>()
+                .join(". ")
+        },
+    };
+    let onpointermove = game::touch::follow(&drag);
+    let onpointerup = match thinking {
+        true => game::touch::cancel(&drag),
+        false => game::touch::end(&drag, &g),
+    };
+    let onpointercancel = game::touch::cancel(&drag);
     html! {
-        <div class="app">
+        <div class={classes!("app", thinking.then_some("app--thinking"))} style={theme.style()} {onpointermove} {onpointerup} {onpointercancel}>
+            {game::theme::render(&theme, &themes)}
+            {history}
             {board}
             {menu}
+            {game::spell::render_detail(&detail)}
+            {game::touch::render(&drag)}
+            <div class="sr-only" role="status" aria-live="polite">{announcement}</div>
         </div>
     }
     // html! {
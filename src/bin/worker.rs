@@ -0,0 +1,8 @@
+use gloo::worker::Registrable;
+use trunk_template::game::ai::Opponent;
+
+/// the computer opponent, thinking in a web worker next to the app
+fn main() {
+    wasm_logger::init(wasm_logger::Config::default());
+    Opponent::registrar().register();
+}
@@ -0,0 +1,194 @@
+use chess::{BoardStatus, Color, Square};
+
+use super::{best, Action, Level, Personality};
+use crate::game::board::square_name;
+use crate::game::notation::san;
+use crate::game::{Game, Kind};
+
+/// hints each side may take per game
+pub const HINTS_PER_GAME: u8 = 3;
+
+/// milliseconds a hint may think
+pub const HINT_MS: f64 = 300.0;
+
+/// Error Type for a hint that can't be given
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HintError {
+    NoneLeft,
+    NothingToDo,
+}
+
+impl std::fmt::Display for HintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HintError::NoneLeft => write!(f, "No hints left this game"),
+            HintError::NothingToDo => write!(f, "There is nothing left to do"),
+        }
+    }
+}
+
+/// The hint in the menu, searched for in the worker and then found
+#[derive(Clone, Debug, PartialEq)]
+pub enum HintState {
+    Searching,
+    Found(Hint),
+}
+
+/// The action the game suggests and why
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hint {
+    pub action: Action,
+    pub explanation: String,
+    /// length of the history when the hint was given, it's stale once anything happened since
+    pub at: usize,
+}
+
+impl Hint {
+    /// the squares to highlight: a move's start and end, a cast's target
+    pub fn squares(&self) -> Vec<Square> {
+        match self.action {
+            Action::Move(m) => vec![m.get_source(), m.get_dest()],
+            Action::Cast(_, square) => vec![square],
+            Action::Respond(_) | Action::Pass => Vec::new(),
+        }
+    }
+
+    /// whether it still applies to `game`
+    pub fn fits(&self, game: &Game) -> bool {
+        self.at == game.history().len()
+    }
+}
+
+fn pieces(count: usize) -> &'static str {
+    match count {
+        1 => "piece",
+        _ => "pieces",
+    }
+}
+
+/// enemy and own pieces other than kings a spell on `square` would catch
+fn caught(game: &Game, area: &[(i32, i32)], square: Square, side: Color) -> (usize, usize) {
+    let board = game.raw_board();
+    let (file, rank) = (square.get_file().to_index() as i32, square.get_rank().to_index() as i32);
+    area.iter()
+        .filter(|(df, dr)| (0..8).contains(&(file + df)) && (0..8).contains(&(rank + dr)))
+        .map(|(df, dr)| Square::make_square(chess::Rank::from_index((rank + dr) as usize), chess::File::from_index((file + df) as usize)))
+        .filter(|square| board.piece_on(*square).is_some_and(|piece| piece != chess::Piece::King))
+        .fold((0, 0), |(enemy, own), square| match board.color_on(square) == Some(side) {
+            true => (enemy, own + 1),
+            false => (enemy + 1, own),
+        })
+}
+
+/// why `action` is good for the acting side, in a sentence
+pub fn explain(game: &Game, action: &Action) -> String {
+    let side = game.acting_side();
+    match *action {
+        Action::Cast(spell, square) => {
+            let Some(spell) = game.held(spell) else { return action.to_string() };
+            let area = spell.area();
+            if area.is_empty() {
+                return format!("{} on {}", spell.name(), square_name(square));
+            }
+            match caught(game, &area, square, side) {
+                (enemy, 0) => format!("{} on {} removes {} enemy {}", spell.name(), square_name(square), enemy, pieces(enemy)),
+                (enemy, own) => format!("{} on {} removes {} enemy {} and {} of yours", spell.name(), square_name(square), enemy, pieces(enemy), own),
+            }
+        },
+        Action::Respond(spell) => {
+            let name = game.held(spell).map(|spell| spell.name()).unwrap_or("?");
+            match game.pending() {
+                Some(cast) => format!("{} answers {:?}'s {}", name, cast.caster, cast.spell.name()),
+                None => name.to_owned(),
+            }
+        },
+        Action::Pass => match game.pending() {
+            Some(cast) => format!("Let {} resolve, answering it isn't worth a spell", cast.spell.name()),
+            None => "Pass".to_owned(),
+        },
+        Action::Move(m) => {
+            let board = game.raw_board();
+            let notation = san(board, m);
+            let mut reasons = Vec::new();
+            if let Some(piece) = board.piece_on(m.get_dest()) {
+                reasons.push(format!("takes a {:?}", piece).to_lowercase());
+            }
+            match game.board().kind_on(m.get_dest()) {
+                Kind::Transparent(spell) => reasons.push(format!("picks up {}", spell.name())),
+                Kind::Opaque(_) => reasons.push("picks up an unknown spell".to_owned()),
+                Kind::None => {},
+            }
+            let after = board.make_move_new(m);
+            if after.status() == BoardStatus::Checkmate {
+                return format!("{} is checkmate", notation);
+            }
+            if after.checkers().popcnt() > 0 {
+                reasons.push("gives check".to_owned());
+            }
+            match reasons.is_empty() {
+                true => format!("{} is the strongest move found", notation),
+                false => format!("{} {}", notation, reasons.join(" and ")),
+            }
+        },
+    }
+}
+
+/// the best action in `seen`, the game as the side asking for a hint sees it
+pub fn suggest(seen: &Game, stop: &dyn Fn() -> bool) -> Option<Action> {
+    best(seen, Level::Master.depth(), Personality::Balanced, stop).map(|(action, _)| action)
+}
+
+impl Game {
+    pub fn hints_left(&self, side: Color) -> u8 {
+        HINTS_PER_GAME.saturating_sub(self.hints[side.to_index()])
+    }
+
+    /// the best action for the acting side as far as it can tell, using up one of its hints
+    pub fn hint(&self, stop: &dyn Fn() -> bool) -> Result<(Game, Hint), HintError> {
+        self.hint_from(|seen| suggest(seen, stop))
+    }
+
+    /// a hint with the action `suggest` finds in the game as the acting side sees it, using up one
+    /// of its hints
+    pub fn hint_from(&self, suggest: impl FnOnce(&Game) -> Option<Action>) -> Result<(Game, Hint), HintError> {
+        let side = self.acting_side();
+        if self.hints_left(side) == 0 {
+            return Err(HintError::NoneLeft);
+        }
+        let action = suggest(&self.as_seen_by(side)).ok_or(HintError::NothingToDo)?;
+        let mut game = self.clone();
+        game.hints[side.to_index()] += 1;
+        let explanation = explain(self, &action);
+        Ok((game, Hint { action, explanation, at: self.history.len() }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explains_traps_by_what_they_catch() {
+        let game = Game::from_fen("4k3/8/8/2pnp3/8/8/8/4K3 w - - 0 1 - W/- - 0 -").unwrap();
+        let cast = "W@d5".parse::<Action>().unwrap();
+        assert_eq!(explain(&game, &cast), "Jihadi Warrior on d5 removes 3 enemy pieces");
+    }
+
+    #[test]
+    fn hints_run_out() {
+        let mut game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        // the mate is found one action deep, a few thousand nodes will do
+        let asked = std::cell::Cell::new(0u32);
+        let stop = || {
+            asked.set(asked.get() + 1);
+            asked.get().is_multiple_of(5)
+        };
+        for _ in 0..HINTS_PER_GAME {
+            let (next, hint) = game.hint(&stop).unwrap();
+            assert_eq!(hint.explanation, "Ra8# is checkmate");
+            assert!(hint.fits(&next));
+            game = next;
+        }
+        assert_eq!(game.hint(&stop).unwrap_err(), HintError::NoneLeft);
+    }
+}
@@ -0,0 +1,543 @@
+//! The computer opponent: an alpha-beta search over everything the acting side may do, moves from
+//! `chess::MoveGen` as well as casting and answering spells, scored by material, spells in hand
+//! and traps lying in wait. Picking up spells needs no action of its own, it comes with the move.
+mod hint;
+mod profile;
+mod worker;
+
+pub use hint::*;
+pub use profile::*;
+pub use worker::*;
+
+use std::str::FromStr;
+
+use chess::{BitBoard, ChessMove, Color, MoveGen, Piece, Square, EMPTY};
+use serde::{Deserialize, Serialize};
+
+use super::board::{parse_square, square_name};
+use super::moves::collect_spell;
+use super::draft::Draft;
+use super::{from_spell_token, restore, spell_token, Game, Speed, Spell};
+
+/// score of a won position, mates found sooner score a little higher
+pub const MATE: i32 = 100_000;
+
+/// centipawns per piece, indexed like `chess::ALL_PIECES`. Kings survive every spell, so they
+/// don't count towards material
+const VALUES: [i32; 6] = [100, 320, 330, 500, 900, 0];
+
+/// how many of the most promising squares a trap is tried on
+const TRAP_SQUARES: usize = 3;
+
+/// A held spell as an action names it, copies of different levels being different spells
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpellId {
+    pub id: u32,
+    pub level: u8,
+}
+
+impl SpellId {
+    pub fn of(spell: &dyn Spell) -> Self {
+        Self { id: spell.identifier(), level: spell.level() }
+    }
+}
+
+/// Something the acting side may do
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Action {
+    Move(ChessMove),
+    /// a sorcery and its target
+    Cast(SpellId, Square),
+    /// answering the pending cast with an instant
+    Respond(SpellId),
+    /// letting the pending cast resolve
+    Pass,
+}
+
+/// Error Type for a string that isn't an action
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionError(String);
+
+impl std::fmt::Display for ActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid action {}", self.0)
+    }
+}
+
+fn token(spell: SpellId) -> String {
+    restore(spell.id, spell.level).map(|spell| spell_token(spell.as_ref())).unwrap_or_else(|| "?".to_owned())
+}
+
+/// written like the PGN commands: `e2e4`, `W2@e5`, `R` or `pass`
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Action::Move(m) => write!(f, "{}", m),
+            Action::Cast(spell, square) => write!(f, "{}@{}", token(*spell), square_name(*square)),
+            Action::Respond(spell) => write!(f, "{}", token(*spell)),
+            Action::Pass => write!(f, "pass"),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = ActionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ActionError(s.to_owned());
+        let spell = |token: &str| from_spell_token(token).map(|spell| SpellId::of(spell.as_ref())).ok_or_else(invalid);
+        match s.split_once('@') {
+            Some((token, square)) => Ok(Action::Cast(spell(token)?, parse_square(square).ok_or_else(invalid)?)),
+            None if s == "pass" => Ok(Action::Pass),
+            // moves take at least four characters, spells a letter and maybe a level
+            None if s.len() <= 2 => Ok(Action::Respond(spell(s)?)),
+            None => ChessMove::from_str(s).map(Action::Move).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl From<Action> for String {
+    fn from(action: Action) -> Self {
+        action.to_string()
+    }
+}
+
+impl TryFrom<String> for Action {
+    type Error = ActionError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl Game {
+    /// the side whose traps may be shown: the player facing `computer`, also while it thinks, or
+    /// whoever acts when two people share the screen
+    pub fn viewer(&self, computer: Option<Computer>) -> Color {
+        computer.map_or_else(|| self.acting_side(), |computer| !computer.side)
+    }
+
+    /// the copy of `spell` held by the side that may act right now
+    pub fn held(&self, spell: SpellId) -> Option<std::sync::Arc<dyn Spell>> {
+        self.spells().into_iter().find(|held| SpellId::of(held.as_ref()) == spell)
+    }
+
+    /// the game after `action`, `None` if it isn't possible right now
+    pub fn apply(&self, action: &Action) -> Option<Game> {
+        match action {
+            Action::Move(m) if self.pending().is_none() && self.raw_board().legal(*m) => {
+                Some(collect_spell(&self.make_move_new(*m), m.get_dest()))
+            },
+            Action::Cast(spell, square) => self.cast_spell(self.held(*spell)?, *square).ok(),
+            Action::Respond(spell) => self.respond(self.held(*spell)?).ok(),
+            Action::Pass if self.pending().is_some() => Some(self.resolve()),
+            _ => None,
+        }
+    }
+
+    /// the game as far as `side` can tell: without the opponent's traps, and with the opponent's
+    /// pending trap landing nowhere since its target is hidden too
+    pub fn as_seen_by(&self, side: Color) -> Game {
+        let mut game = Game {
+            history: Vec::new(),
+            ..self.clone()
+        };
+        game.casted_mut(!side).clear();
+        for cast in game.stack.iter_mut().filter(|cast| cast.caster != side) {
+            if cast.square.is_some_and(|square| self.trap_for(cast.spell.as_ref(), square).is_some()) {
+                cast.square = None;
+            }
+        }
+        game
+    }
+}
+
+fn value(piece: Piece) -> i32 {
+    VALUES[piece.to_index()]
+}
+
+/// a little for minor pieces and pawns near the center, and for pawns closer to promotion
+fn placement(piece: Piece, square: Square, color: Color) -> i32 {
+    let (file, rank) = (square.get_file().to_index() as i32, square.get_rank().to_index() as i32);
+    let center = 14 - (2 * file - 7).abs() - (2 * rank - 7).abs();
+    let advanced = match color {
+        Color::White => rank - 1,
+        Color::Black => 6 - rank,
+    };
+    match piece {
+        Piece::Pawn => center + 4 * advanced,
+        Piece::Knight | Piece::Bishop => 2 * center,
+        _ => 0,
+    }
+}
+
+fn side_score(game: &Game, color: Color, personality: Personality) -> i32 {
+    let board = game.raw_board();
+    let pieces = board.color_combined(color).map(|square| {
+        let piece = board.piece_on(square).unwrap_or(Piece::King);
+        value(piece) + placement(piece, square, color)
+    }).sum::<i32>();
+    let spells = game.inventory(color).spells().iter().map(|spell| spell.worth()).sum::<i32>();
+    let traps = game.casted(color).values().map(|cast| cast.spell.worth()).sum::<i32>();
+    pieces + (spells * personality.held() + traps * personality.armed()) / 100
+}
+
+/// how good the game looks for `color` to a player of `personality`, in centipawns
+pub fn evaluate(game: &Game, color: Color, personality: Personality) -> i32 {
+    side_score(game, color, personality) - side_score(game, !color, personality)
+}
+
+/// what a trap on `square` would blow up, the opponent's pieces counting for and the own against
+fn blast(game: &Game, spell: &dyn Spell, square: Square, color: Color) -> i32 {
+    let board = game.raw_board();
+    let (file, rank) = (square.get_file().to_index() as i32, square.get_rank().to_index() as i32);
+    spell.area().iter().filter_map(|(df, dr)| {
+        let (file, rank) = (file + df, rank + dr);
+        if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+            return None;
+        }
+        let square = Square::make_square(chess::Rank::from_index(rank as usize), chess::File::from_index(file as usize));
+        let piece = value(board.piece_on(square)?);
+        Some(if board.color_on(square) == Some(color) { -piece } else { piece })
+    }).sum()
+}
+
+/// squares worth casting `spell` on: where the opponent's next moves lead to lay a trap, or its
+/// pieces to go off at once, best blasts first
+fn targets(game: &Game, spell: &dyn Spell) -> Vec<Square> {
+    let board = game.raw_board();
+    let color = board.side_to_move();
+    let replies = match board.null_move() {
+        Some(board) => MoveGen::new_legal(&board).collect::<Vec<_>>(),
+        None => return Vec::new(),
+    };
+    let paths = replies.iter().fold(EMPTY, |paths, m| {
+        paths | chess::between(m.get_source(), m.get_dest()) | BitBoard::from_square(m.get_dest())
+    });
+    let mut scored = (paths | *board.color_combined(!color))
+        .filter(|square| game.trap_on(color, *square).is_none())
+        .map(|square| (blast(game, spell, square, color), square))
+        .filter(|(score, _)| *score > 0)
+        .collect::<Vec<_>>();
+    scored.sort_by_key(|(score, square)| (-score, *square));
+    scored.into_iter().take(TRAP_SQUARES).map(|(_, square)| square).collect()
+}
+
+/// captures by value of the victim, then of the attacker, then promotions
+fn capture_order(game: &Game, m: &ChessMove) -> i32 {
+    let board = game.raw_board();
+    let victim = board.piece_on(m.get_dest()).map(value).unwrap_or_default();
+    let attacker = board.piece_on(m.get_source()).map(value).unwrap_or_default();
+    let promotion = m.get_promotion().map(value).unwrap_or_default();
+    10 * victim - attacker / 10 + promotion
+}
+
+fn captures(game: &Game) -> Vec<Action> {
+    let board = game.raw_board();
+    let mut moves = MoveGen::new_legal(board)
+        .filter(|m| board.piece_on(m.get_dest()).is_some() || m.get_promotion().is_some())
+        .collect::<Vec<_>>();
+    moves.sort_by_key(|m| -capture_order(game, m));
+    moves.into_iter().map(Action::Move).collect()
+}
+
+/// everything the acting side may do as `personality` sees it, the likeliest good actions first
+pub fn actions(game: &Game, personality: Personality) -> Vec<Action> {
+    let spells = match personality.casts() {
+        true => game.spells(),
+        false => Vec::new(),
+    };
+    if game.pending().is_some() {
+        let responses = spells.iter()
+            .filter(|spell| spell.speed() == Speed::Instant)
+            .map(|spell| Action::Respond(SpellId::of(spell.as_ref())))
+            .collect::<Vec<_>>();
+        return [vec![Action::Pass], responses].concat();
+    }
+    let board = game.raw_board();
+    let mut moves = MoveGen::new_legal(board).collect::<Vec<_>>();
+    moves.sort_by_key(|m| -capture_order(game, m));
+    let (loud, quiet): (Vec<_>, Vec<_>) = moves.into_iter().partition(|m| capture_order(game, m) > 0);
+    let casts = spells.iter()
+        .filter(|spell| game.can_cast(spell.as_ref()).is_ok())
+        .flat_map(|spell| targets(game, spell.as_ref()).into_iter().map(|square| Action::Cast(SpellId::of(spell.as_ref()), square)))
+        .collect::<Vec<_>>();
+    loud.into_iter().map(Action::Move).chain(casts).chain(quiet.into_iter().map(Action::Move)).collect()
+}
+
+/// scores `next` for `side` by searching it with bounds seen from whoever acts there
+fn for_side(side: Color, next: &Game, alpha: i32, beta: i32, search: impl FnOnce(i32, i32) -> i32) -> i32 {
+    match next.acting_side() == side {
+        true => search(alpha, beta),
+        false => -search(-beta, -alpha),
+    }
+}
+
+/// One search, given up as soon as `stop` says time is over
+struct Search<'a> {
+    personality: Personality,
+    stop: &'a dyn Fn() -> bool,
+    nodes: u32,
+    stopped: bool,
+}
+
+impl Search<'_> {
+    /// counts a node, asking `stop` every so often since the clock isn't free
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.stopped && self.nodes.is_multiple_of(256) {
+            self.stopped = (self.stop)();
+        }
+        self.stopped
+    }
+
+    /// captures only, so the search doesn't stop in the middle of an exchange
+    fn quiesce(&mut self, game: &Game, mut alpha: i32, beta: i32) -> i32 {
+        let side = game.acting_side();
+        if game.pending().is_some() {
+            let resolved = game.resolve();
+            return for_side(side, &resolved, alpha, beta, |alpha, beta| self.quiesce(&resolved, alpha, beta));
+        }
+        let stand = evaluate(game, side, self.personality);
+        if stand >= beta || self.visit() {
+            return stand;
+        }
+        alpha = alpha.max(stand);
+        for action in captures(game) {
+            let Some(next) = game.apply(&action) else { continue };
+            let score = for_side(side, &next, alpha, beta, |alpha, beta| self.quiesce(&next, alpha, beta));
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    fn negamax(&mut self, game: &Game, depth: u8, mut alpha: i32, beta: i32, ply: i32) -> i32 {
+        if game.pending().is_none() {
+            match game.raw_board().status() {
+                chess::BoardStatus::Checkmate => return -MATE + ply,
+                chess::BoardStatus::Stalemate => return 0,
+                chess::BoardStatus::Ongoing => {},
+            }
+        }
+        if depth == 0 || self.visit() {
+            return self.quiesce(game, alpha, beta);
+        }
+        let side = game.acting_side();
+        let mut best = -MATE;
+        for action in actions(game, self.personality) {
+            let Some(next) = game.apply(&action) else { continue };
+            let score = for_side(side, &next, alpha, beta, |alpha, beta| self.negamax(&next, depth - 1, alpha, beta, ply + 1));
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    /// the best action `depth` actions deep, trying `first` before the rest
+    fn root(&mut self, game: &Game, depth: u8, first: Option<Action>) -> Option<(Action, i32)> {
+        let side = game.acting_side();
+        let mut actions = actions(game, self.personality);
+        if let Some(index) = first.and_then(|first| actions.iter().position(|action| *action == first)) {
+            let action = actions.remove(index);
+            actions.insert(0, action);
+        }
+        let mut alpha = -MATE - 1;
+        let mut best = None;
+        for action in actions {
+            let Some(next) = game.apply(&action) else { continue };
+            let score = for_side(side, &next, alpha, MATE + 1, |alpha, beta| self.negamax(&next, depth.saturating_sub(1), alpha, beta, 1));
+            if score > alpha {
+                alpha = score;
+                best = Some((action, score));
+            }
+        }
+        best
+    }
+}
+
+/// the best action for the acting side and its score, `None` once the game is over. Searches one
+/// action deeper at a time up to `depth`, keeping the deepest result finished before `stop`
+pub fn best(game: &Game, depth: u8, personality: Personality, stop: &dyn Fn() -> bool) -> Option<(Action, i32)> {
+    let mut search = Search { personality, stop, nodes: 0, stopped: false };
+    let mut best = None;
+    for depth in 1..=depth.max(1) {
+        let found = search.root(game, depth, best.map(|(action, _)| action));
+        if search.stopped && best.is_some() {
+            break;
+        }
+        best = found.or(best);
+        if search.stopped {
+            break;
+        }
+    }
+    best
+}
+
+/// xorshift64, good enough to roll for a blunder and pick one
+struct Dice(u64);
+
+impl Dice {
+    fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    fn roll(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// whether a computer at `level` passes up the best action this time
+    fn blunders(&mut self, level: Level) -> bool {
+        self.roll() % 100 < level.blunder_rate() as u64
+    }
+}
+
+/// what the computer plays: usually the best action, sometimes on purpose something else
+pub fn choose(game: &Game, level: Level, personality: Personality, seed: u64, stop: &dyn Fn() -> bool) -> Option<Action> {
+    let (best, _) = best(game, level.depth(), personality, stop)?;
+    let mut dice = Dice::new(seed);
+    if !dice.blunders(level) {
+        return Some(best);
+    }
+    let others = actions(game, personality).into_iter()
+        .filter(|action| *action != best && game.apply(action).is_some())
+        .collect::<Vec<_>>();
+    match others.is_empty() {
+        true => Some(best),
+        false => Some(others[(dice.roll() % others.len() as u64) as usize]),
+    }
+}
+
+/// the spell the computer bans or picks next in a draft: the strongest, to keep it from the opponent
+pub fn draft_choice(draft: &Draft) -> usize {
+    draft.pool().iter().enumerate()
+        .max_by_key(|(i, spell)| (spell.worth(), std::cmp::Reverse(*i)))
+        .map(|(i, _)| i)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{few_nodes, opening, square};
+    use crate::game::Inventory;
+
+    #[test]
+    fn the_computers_traps_stay_hidden_while_it_thinks() {
+        // black armed a warrior on e5 and white is to move
+        let game = opening();
+        assert_eq!(game.viewer(None), Color::White);
+        assert_eq!(game.viewer(Some(Computer::new(Color::White))), Color::Black);
+        assert_eq!(game.viewer(Some(Computer::new(Color::Black))), Color::White);
+        assert!(game.traps(game.viewer(Some(Computer::new(Color::Black)))).is_empty());
+    }
+
+    #[test]
+    fn actions_round_trip_as_text() {
+        for text in ["e2e4", "e7e8q", "W@e5", "W2@e5", "R", "pass"] {
+            assert_eq!(text.parse::<Action>().unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn casts_the_level_named() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 - W3W/- - 0 -").unwrap();
+        for (cast, kept) in [("W@e5", 3), ("W3@e5", 1)] {
+            let next = game.apply(&cast.parse().unwrap()).unwrap();
+            assert_eq!(next.inventory(Color::White).spells()[0].level(), kept);
+        }
+        assert_eq!(game.apply(&"W2@e5".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn takes_a_hanging_queen() {
+        let game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/4P2q/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1").unwrap();
+        let (action, _) = best(&game, 2, Personality::Balanced, &|| false).unwrap();
+        assert_eq!(action, Action::Move(ChessMove::new(square("f3"), square("h4"), None)));
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let (action, score) = best(&game, 1, Personality::Balanced, &|| false).unwrap();
+        assert_eq!(action, Action::Move(ChessMove::new(square("a1"), square("a8"), None)));
+        assert!(score > MATE - 10);
+    }
+
+    #[test]
+    fn picks_up_spells() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 W@e4 -/- - 0 -").unwrap();
+        let (action, _) = best(&game, 1, Personality::Balanced, &|| false).unwrap();
+        assert_eq!(action, Action::Move(ChessMove::new(square("e2"), square("e4"), None)));
+    }
+
+    #[test]
+    fn lays_traps_where_the_opponent_goes() {
+        // black's rook and bishop can only leave through the squares next to them
+        let game = Game::from_fen("1b6/1r6/8/8/8/8/8/K6k w - - 0 1 - W/- - 0 -").unwrap();
+        let casts = actions(&game, Personality::Balanced).into_iter().filter(|action| matches!(action, Action::Cast(..))).collect::<Vec<_>>();
+        assert!(!casts.is_empty());
+        assert!(casts.len() <= TRAP_SQUARES);
+    }
+
+    #[test]
+    fn purists_never_cast() {
+        let game = Game::from_fen("1b6/1r6/8/8/8/8/8/K6k w - - 0 1 - W/- - 0 -").unwrap();
+        assert!(actions(&game, Personality::Purist).iter().all(|action| matches!(action, Action::Move(_))));
+    }
+
+    /// seeds spread over the whole range, like the ones the app rolls
+    fn seeds(count: u64) -> impl Iterator<Item = u64> {
+        (0..count).map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    #[test]
+    fn masters_dont_blunder() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        for seed in 0..5 {
+            // the mate is found one action deep
+            let action = choose(&game, Level::Master, Personality::Balanced, seed, &few_nodes());
+            assert_eq!(action, Some(Action::Move(ChessMove::new(square("a1"), square("a8"), None))));
+        }
+    }
+
+    #[test]
+    fn levels_blunder_at_their_rate() {
+        for level in [Level::Beginner, Level::Casual, Level::Club, Level::Master] {
+            let blunders = seeds(1000).filter(|seed| Dice::new(*seed).blunders(level)).count();
+            // 10 rolls per percent, give or take what 1000 rolls may stray
+            let expected = level.blunder_rate() as usize * 10;
+            assert!(blunders.abs_diff(expected) <= 40, "{:?}: {}", level, blunders);
+        }
+    }
+
+    #[test]
+    fn blunders_pass_up_the_best_action() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        // the mate is found one action deep
+        let mate = Some(Action::Move(ChessMove::new(square("a1"), square("a8"), None)));
+        let seed = seeds(100).find(|seed| Dice::new(*seed).blunders(Level::Beginner)).unwrap();
+        let blunder = choose(&game, Level::Beginner, Personality::Balanced, seed, &few_nodes());
+        assert!(blunder.is_some_and(|action| Some(action) != mate && game.apply(&action).is_some()));
+        // the same roll leaves a master on the mate
+        assert_eq!(choose(&game, Level::Master, Personality::Balanced, seed, &few_nodes()), mate);
+    }
+
+    #[test]
+    fn doesnt_see_the_opponents_traps() {
+        let game = Game::new().with_inventories([Inventory::test(), Inventory::test()]);
+        let warrior = game.spells()[0].clone();
+        let game = game.cast_spell(warrior, square("e5")).unwrap().resolve();
+        assert_eq!(game.traps(Color::White).len(), 1);
+        assert!(game.as_seen_by(Color::Black).traps(Color::White).is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use chess::Color;
+use serde::{Deserialize, Serialize};
+
+/// How strong the computer plays
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Level {
+    Beginner,
+    Casual,
+    #[default]
+    Club,
+    Master,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Beginner, Level::Casual, Level::Club, Level::Master];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Beginner => "Beginner",
+            Level::Casual => "Casual",
+            Level::Club => "Club",
+            Level::Master => "Master",
+        }
+    }
+
+    /// how many actions ahead the search looks at most
+    pub fn depth(&self) -> u8 {
+        match self {
+            Level::Beginner => 1,
+            Level::Casual => 2,
+            Level::Club => 4,
+            Level::Master => 6,
+        }
+    }
+
+    /// milliseconds to think before settling for the deepest search finished so far
+    pub fn time_ms(&self) -> f64 {
+        match self {
+            Level::Beginner => 200.0,
+            Level::Casual => 500.0,
+            Level::Club => 1_500.0,
+            Level::Master => 4_000.0,
+        }
+    }
+
+    /// chance in percent of deliberately playing something other than the best action
+    pub fn blunder_rate(&self) -> u8 {
+        match self {
+            Level::Beginner => 30,
+            Level::Casual => 15,
+            Level::Club => 5,
+            Level::Master => 0,
+        }
+    }
+}
+
+/// What the computer likes to do with its spells
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Personality {
+    #[default]
+    Balanced,
+    /// casts whatever it can, traps count for more than spells in hand
+    Aggressive,
+    /// keeps its spells, casting only when it pays off handsomely
+    Hoarder,
+    /// plays plain chess, never casting or responding
+    Purist,
+}
+
+impl Personality {
+    pub const ALL: [Personality; 4] = [Personality::Balanced, Personality::Aggressive, Personality::Hoarder, Personality::Purist];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Personality::Balanced => "Balanced",
+            Personality::Aggressive => "Aggressive spell-caster",
+            Personality::Hoarder => "Hoarder",
+            Personality::Purist => "Pure chess player",
+        }
+    }
+
+    /// percent of its worth a spell in hand counts for
+    pub fn held(&self) -> i32 {
+        match self {
+            Personality::Balanced => 100,
+            Personality::Aggressive => 60,
+            Personality::Hoarder => 160,
+            Personality::Purist => 0,
+        }
+    }
+
+    /// percent of its worth an armed trap counts for
+    pub fn armed(&self) -> i32 {
+        match self {
+            Personality::Balanced => 100,
+            Personality::Aggressive => 150,
+            Personality::Hoarder => 80,
+            Personality::Purist => 0,
+        }
+    }
+
+    pub fn casts(&self) -> bool {
+        *self != Personality::Purist
+    }
+}
+
+/// The computer opponent's side and style
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Computer {
+    pub side: Color,
+    pub level: Level,
+    pub personality: Personality,
+}
+
+impl Computer {
+    pub fn new(side: Color) -> Self {
+        Self {
+            side,
+            level: Level::default(),
+            personality: Personality::default(),
+        }
+    }
+}
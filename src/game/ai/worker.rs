@@ -0,0 +1,95 @@
+use gloo::worker::{HandlerId, Spawnable, Worker, WorkerBridge, WorkerScope};
+use serde::{Deserialize, Serialize};
+
+use super::{choose, suggest, Action, Computer, Level, Personality, HINT_MS};
+use crate::game::analysis::{review, ANALYSIS_MS};
+use crate::game::Game;
+
+/// the script trunk builds from `src/bin/worker.rs`, next to the app's own
+const SCRIPT: &str = "worker.js";
+
+/// What a position is searched for
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Purpose {
+    /// the computer's next action, the seed is for the rolls deciding on blunders
+    Play { level: Level, personality: Personality, seed: u64 },
+    /// the best action, for a hint
+    Hint,
+    /// the best action and the score, for the analysis
+    Review,
+}
+
+/// A position to think about, as Magic FEN of what the searching side may know
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Think {
+    pub fen: String,
+    pub purpose: Purpose,
+}
+
+/// The answer to a `Think`, `None` if there was nothing to do
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Thought {
+    pub fen: String,
+    pub action: Option<Action>,
+    /// centipawns from white's side, only reviews are scored
+    pub score: Option<i32>,
+}
+
+/// The computer opponent, the hints and the analysis, searching in a web worker so the board stays responsive
+pub struct Opponent;
+
+impl Worker for Opponent {
+    type Message = ();
+    type Input = Think;
+    type Output = Thought;
+
+    fn create(_scope: &WorkerScope<Self>) -> Self {
+        Self
+    }
+
+    fn update(&mut self, _scope: &WorkerScope<Self>, _msg: Self::Message) {}
+
+    fn received(&mut self, scope: &WorkerScope<Self>, msg: Self::Input, id: HandlerId) {
+        let (action, score) = match (Game::from_fen(&msg.fen), msg.purpose) {
+            (Ok(game), Purpose::Play { level, personality, seed }) => {
+                let until = js_sys::Date::now() + level.time_ms();
+                (choose(&game, level, personality, seed, &|| js_sys::Date::now() > until), None)
+            },
+            (Ok(game), Purpose::Hint) => {
+                let until = js_sys::Date::now() + HINT_MS;
+                (suggest(&game, &|| js_sys::Date::now() > until), None)
+            },
+            (Ok(game), Purpose::Review) => {
+                let until = js_sys::Date::now() + ANALYSIS_MS;
+                let review = review(&game, &|| js_sys::Date::now() > until);
+                (review.best, Some(review.score))
+            },
+            (Err(e), _) => {
+                log::warn!("worker can't read {}: {}", msg.fen, e);
+                (None, None)
+            },
+        };
+        scope.respond(id, Thought { fen: msg.fen, action, score });
+    }
+}
+
+/// starts a worker, `reply` gets every `Thought`
+pub fn spawn(reply: impl Fn(Thought) + 'static) -> WorkerBridge<Opponent> {
+    Opponent::spawner().callback(reply).spawn(SCRIPT)
+}
+
+/// what `computer` may know of the game, to send to its worker
+pub fn think(game: &Game, computer: &Computer, seed: u64) -> Think {
+    Think {
+        fen: game.as_seen_by(computer.side).to_fen(),
+        purpose: Purpose::Play { level: computer.level, personality: computer.personality, seed },
+    }
+}
+
+/// what the acting side knows of the game, to search for a hint
+pub fn ask_hint(game: &Game) -> Think {
+    Think {
+        fen: game.as_seen_by(game.acting_side()).to_fen(),
+        purpose: Purpose::Hint,
+    }
+}
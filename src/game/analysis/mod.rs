@@ -0,0 +1,304 @@
+//! Looking back at a finished game: every decision is replayed from the history and searched by
+//! the computer opponent's engine, with every trap revealed, then judged by what it gave away.
+//! Positions are kept as parsed from their Magic FEN, without the history leading to them, and
+//! reviewed in the worker one at a time.
+pub mod render;
+
+use chess::{BoardStatus, ChessMove, Color};
+
+pub use render::*;
+use super::ai::{best, evaluate, Action, Level, Personality, SpellId, MATE};
+use super::fen::FenError;
+use super::history::{Event, Recorded};
+use super::Game;
+
+/// milliseconds each position may be searched
+pub const ANALYSIS_MS: f64 = 250.0;
+
+/// centipawns a decision must lose to count as a blunder
+const BLUNDER: i32 = 250;
+
+/// centipawns ignoring the best action, a spell, must lose to count as a missed opportunity
+const MISSED: i32 = 100;
+
+/// centipawns a cast must win over the quiet evaluation before it to count as brilliant
+const BRILLIANT: i32 = 300;
+
+/// Error Type for a game that can't be taken apart
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnalysisError {
+    Start(FenError),
+    /// a history entry that doesn't replay from the one before, by index
+    Replay(usize),
+}
+
+impl std::fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AnalysisError::Start(e) => write!(f, "Can't set up the start: {}", e),
+            AnalysisError::Replay(index) => write!(f, "History entry {} can't be replayed", index),
+        }
+    }
+}
+
+/// What a decision amounted to, if anything worth pointing out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Blunder,
+    /// a move or pass when casting or answering would have been much better
+    MissedSpell,
+    /// a cast as good as the engine's best that swings the game
+    BrilliantCast,
+}
+
+impl Verdict {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Verdict::Blunder => "Blunder",
+            Verdict::MissedSpell => "Missed spell",
+            Verdict::BrilliantCast => "Brilliant cast",
+        }
+    }
+
+    /// the annotation next to the decision in the list
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Verdict::Blunder => "??",
+            Verdict::MissedSpell => "✦?",
+            Verdict::BrilliantCast => "!!",
+        }
+    }
+}
+
+/// One decision of the game
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub action: Action,
+    pub mover: Color,
+    /// the history entry it was recorded as, `None` for letting a cast resolve
+    pub entry: Option<usize>,
+}
+
+/// The engine's opinion of a position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Review {
+    pub best: Option<Action>,
+    /// centipawns, from white's side
+    pub score: i32,
+}
+
+/// A game taken apart decision by decision, reviewed one position at a time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    /// the start and the position after each step, without history
+    positions: Vec<Game>,
+    steps: Vec<Step>,
+    /// for the positions reviewed so far, in order
+    reviews: Vec<Review>,
+}
+
+impl Game {
+    /// whether the board is checkmate or stalemate with nothing left on the stack
+    pub fn is_over(&self) -> bool {
+        self.pending().is_none() && self.raw_board().status() != BoardStatus::Ongoing
+    }
+}
+
+/// the decision the entry was recorded from, `None` for things that merely happened
+fn played(event: &Event) -> Option<Recorded<ChessMove>> {
+    match event {
+        Event::Move { m, .. } => Some(Recorded::Move(*m)),
+        Event::Cast { spell, square: Some(square), .. } => Some(Recorded::Cast(SpellId::of(spell.as_ref()), *square)),
+        Event::Respond { spell, .. } => Some(Recorded::Respond(SpellId::of(spell.as_ref()))),
+        _ => None,
+    }
+}
+
+/// `game` without the history leading to it
+fn position(game: &Game, entry: usize) -> Result<Game, AnalysisError> {
+    Game::from_fen(&game.to_fen()).map_err(|_| AnalysisError::Replay(entry))
+}
+
+/// what the engine makes of `game`
+pub fn review(game: &Game, stop: &dyn Fn() -> bool) -> Review {
+    let white = |score: i32| match game.acting_side() {
+        Color::White => score,
+        Color::Black => -score,
+    };
+    match best(game, Level::Master.depth(), Personality::Balanced, stop) {
+        Some((action, score)) => Review { best: Some(action), score: white(score) },
+        None => match game.raw_board().status() {
+            BoardStatus::Checkmate => Review { best: None, score: white(-MATE) },
+            _ => Review { best: None, score: 0 },
+        },
+    }
+}
+
+impl Analysis {
+    /// replays the history of `game`, decision by decision
+    pub fn new(game: &Game) -> Result<Self, AnalysisError> {
+        let start = game.start().map_err(AnalysisError::Start)?;
+        let (entries, records): (Vec<_>, Vec<_>) = game.history().iter().enumerate()
+            .filter_map(|(i, entry)| played(&entry.event).map(|record| (i, record)))
+            .unzip();
+        let replayed = start.replay(records, |_, m| Some(*m)).map_err(|i| AnalysisError::Replay(entries[i]))?;
+        let mut positions = vec![position(&start, 0)?];
+        let mut steps = Vec::new();
+        let mut at = 0;
+        for step in replayed {
+            let entry = step.record.map(|record| entries[record]);
+            at = entry.unwrap_or(at);
+            steps.push(Step { action: step.action, mover: step.mover, entry });
+            positions.push(position(&step.game, at)?);
+        }
+        Ok(Self { positions, steps, reviews: Vec::new() })
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// how many positions were reviewed so far
+    pub fn reviewed(&self) -> usize {
+        self.reviews.len()
+    }
+
+    pub fn done(&self) -> bool {
+        self.reviews.len() == self.positions.len()
+    }
+
+    /// Magic FEN of the next position to review, for the worker
+    pub fn next(&self) -> Option<String> {
+        self.positions.get(self.reviews.len()).map(Game::to_fen)
+    }
+
+    /// takes the worker's review of `fen`, unless it isn't the next position any more
+    pub fn add_review(&mut self, fen: &str, review: Review) -> bool {
+        let next = self.next().is_some_and(|next| next == fen);
+        if next {
+            self.reviews.push(review);
+        }
+        next
+    }
+
+    /// reviews the next position right here, searching until `stop`
+    pub fn review_next(&mut self, stop: &dyn Fn() -> bool) {
+        if let Some(game) = self.positions.get(self.reviews.len()) {
+            self.reviews.push(review(game, stop));
+        }
+    }
+
+    /// the position after `steps` steps, the last one past the end of the game
+    pub fn position(&self, steps: usize) -> &Game {
+        &self.positions[steps.min(self.steps.len())]
+    }
+
+    /// the review of the position after `steps` steps, once there is one
+    pub fn review(&self, steps: usize) -> Option<&Review> {
+        self.reviews.get(steps)
+    }
+
+    /// what step `i` amounted to, once the positions before and after it are reviewed
+    pub fn verdict(&self, i: usize) -> Option<Verdict> {
+        let (before, after) = (self.reviews.get(i)?, self.reviews.get(i + 1)?);
+        let step = &self.steps[i];
+        let mine = |score: i32| match step.mover {
+            Color::White => score,
+            Color::Black => -score,
+        };
+        let lost = mine(before.score) - mine(after.score);
+        let spell = |action: &Action| matches!(action, Action::Cast(..) | Action::Respond(_));
+        if matches!(step.action, Action::Cast(..)) && lost <= 0 {
+            let quiet = evaluate(&self.positions[i], step.mover, Personality::Balanced);
+            if mine(after.score) - quiet >= BRILLIANT {
+                return Some(Verdict::BrilliantCast);
+            }
+        }
+        if !spell(&step.action) && before.best.as_ref().is_some_and(spell) && lost >= MISSED {
+            return Some(Verdict::MissedSpell);
+        }
+        (lost >= BLUNDER).then_some(Verdict::Blunder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::square;
+
+    #[test]
+    fn replays_the_history() {
+        let mut game = Game::from_fen("4k3/8/8/2pnp3/8/8/8/4K3 w - - 0 1 - W/- - 0 -").unwrap();
+        let cast = "W@d5".parse::<Action>().unwrap();
+        game = game.apply(&cast).unwrap();
+        let m = Action::Move(chess::ChessMove::new(square("e1"), square("f2"), None));
+        game = game.resolve().apply(&m).unwrap();
+        let analysis = Analysis::new(&game).unwrap();
+        let actions = analysis.steps().iter().map(|step| step.action).collect::<Vec<_>>();
+        assert_eq!(actions, vec![cast, Action::Pass, m]);
+        assert_eq!(analysis.steps()[1].mover, Color::Black);
+        assert_eq!(analysis.positions.last().unwrap().to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn judges_decisions_by_what_they_lost() {
+        let game = Game::from_fen("4k3/8/8/2pnp3/8/8/P7/4K3 w - - 0 1 - W/- - 0 -").unwrap();
+        let push = Action::Move(chess::ChessMove::new(square("a2"), square("a3"), None));
+        let cast = "W@d5".parse::<Action>().unwrap();
+        let mut analysis = Analysis::new(&game.apply(&push).unwrap()).unwrap();
+        let judge = |analysis: &mut Analysis, best: Action, before: i32, after: i32| {
+            analysis.reviews = vec![Review { best: Some(best), score: before }, Review { best: None, score: after }];
+            analysis.verdict(0)
+        };
+        assert_eq!(judge(&mut analysis, cast, 400, 0), Some(Verdict::MissedSpell));
+        assert_eq!(judge(&mut analysis, push, 400, 0), Some(Verdict::Blunder));
+        assert_eq!(judge(&mut analysis, push, 400, 350), None);
+
+        let mut analysis = Analysis::new(&game.apply(&cast).unwrap()).unwrap();
+        assert_eq!(judge(&mut analysis, cast, 700, 700), Some(Verdict::BrilliantCast));
+        // as good as it looked before, the cast just kept it that way
+        assert_eq!(judge(&mut analysis, cast, -300, -300), None);
+    }
+
+    #[test]
+    fn reviews_every_position() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mate = Action::Move(chess::ChessMove::new(square("a1"), square("a8"), None));
+        let mut analysis = Analysis::new(&game.apply(&mate).unwrap()).unwrap();
+        // the mate is found one action deep, a few thousand nodes will do
+        let asked = std::cell::Cell::new(0u32);
+        let stop = || {
+            asked.set(asked.get() + 1);
+            asked.get().is_multiple_of(5)
+        };
+        while !analysis.done() {
+            analysis.review_next(&stop);
+        }
+        assert_eq!(analysis.review(0).unwrap().best, Some(mate));
+        assert_eq!(analysis.review(1).unwrap().score, MATE);
+        assert_eq!(analysis.verdict(0), None);
+    }
+
+    #[test]
+    fn reviews_only_the_next_position() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mate = Action::Move(chess::ChessMove::new(square("a1"), square("a8"), None));
+        let mut analysis = Analysis::new(&game.apply(&mate).unwrap()).unwrap();
+        let review = Review { best: Some(mate), score: 0 };
+        let after = analysis.position(1).to_fen();
+        assert!(!analysis.add_review(&after, review));
+        let next = analysis.next().unwrap();
+        assert!(analysis.add_review(&next, review));
+        assert_eq!(analysis.next(), Some(after));
+    }
+
+    #[test]
+    fn reports_what_doesnt_replay() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        game = game.make_move_new(chess::ChessMove::new(square("e1"), square("d1"), None));
+        game.origin = "not a position".to_owned();
+        assert!(matches!(Analysis::new(&game), Err(AnalysisError::Start(_))));
+        game.origin = "4k3/8/8/8/8/8/8/3K4 w - - 0 1".to_owned();
+        assert_eq!(Analysis::new(&game), Err(AnalysisError::Replay(0)));
+    }
+}
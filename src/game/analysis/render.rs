@@ -0,0 +1,106 @@
+use yew::prelude::*;
+
+use super::{Analysis, Step, Verdict};
+use crate::game::ai::{explain, MATE};
+use crate::game::{board, theme, Game};
+
+/// centipawns where the bar is all one color
+const BAR_LIMIT: i32 = 1000;
+
+/// a score from white's side as it reads on the bar, e.g. "+1.3" or "-#2"
+fn score_text(score: i32) -> String {
+    let sign = if score < 0 { "-" } else { "+" };
+    match MATE - score.abs() {
+        // plies until mate, counted in moves of the winner
+        plies if plies < 1000 => format!("{}#{}", sign, (plies + 1) / 2),
+        _ => format!("{}{:.1}", sign, score.abs() as f64 / 100.0),
+    }
+}
+
+fn render_bar(analysis: &Analysis, at: usize) -> Html {
+    let (white, text) = match analysis.review(at) {
+        Some(review) => (50 + review.score.clamp(-BAR_LIMIT, BAR_LIMIT) / (BAR_LIMIT / 50), score_text(review.score)),
+        None => (50, "…".to_owned()),
+    };
+    html! {
+        <div class="analysis--bar" role="meter" aria-label="Evaluation" aria-valuemin="0" aria-valuemax="100" aria-valuenow={white.to_string()} aria-valuetext={text.clone()}>
+            <div class="analysis--bar--white" style={format!("height: {}%;", white)}></div>
+            <span class="analysis--bar--score text-sm">{text}</span>
+        </div>
+    }
+}
+
+fn mark_class(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Blunder => "analysis--mark--blunder",
+        Verdict::MissedSpell => "analysis--mark--missed",
+        Verdict::BrilliantCast => "analysis--mark--brilliant",
+    }
+}
+
+/// how step `i` reads in the list, with every hidden cast revealed
+fn label(game: &Game, step: &Step) -> String {
+    match step.entry.and_then(|entry| game.history().get(entry)) {
+        Some(entry) => entry.label(step.mover),
+        None => "pass".to_owned(),
+    }
+}
+
+/// the verdict on the step leading to the position on display and what the engine would have done
+fn render_verdict(analysis: &Analysis, at: usize) -> Html {
+    let Some(i) = at.checked_sub(1) else { return html! {} };
+    let best = analysis.review(i).and_then(|review| review.best).map(|best| explain(analysis.position(i), &best));
+    html! {
+        <div class="analysis--verdict" role="status">
+            if let Some(verdict) = analysis.verdict(i) {
+                <span class={classes!("analysis--mark", mark_class(verdict))}>{format!("{} {}", verdict.symbol(), verdict.name())}</span>
+            }
+            if let Some(best) = best {
+                <span class="text-sm">{format!("Best: {}", best)}</span>
+            }
+        </div>
+    }
+}
+
+/// the analysis view, stepping through `game` with the board, evaluation bar and annotated list
+pub fn render(game: &UseStateHandle<Game>, analysis: &UseStateHandle<Option<Analysis>>, step: &UseStateHandle<usize>, theme: &theme::Theme) -> Html {
+    let Some(current) = analysis.as_ref() else { return html! {} };
+    let at = (**step).min(current.steps().len());
+    let go = |to: usize| {
+        let step = step.clone();
+        Callback::from(move |_: MouseEvent| step.set(to))
+    };
+    let close = {
+        let analysis = analysis.clone();
+        Callback::from(move |_| analysis.set(None))
+    };
+    let last = current.steps().len();
+    html! {
+        <div class="analysis">
+            {render_bar(current, at)}
+            {board::render_past(game, current.position(at).board(), theme)}
+            <div class="analysis--panel p-6 shadow-2xl rounded-xl">
+                <div class="history--actions">
+                    <button class="menu--pass rounded-lg" onclick={go(0)} disabled={at == 0} aria-label="Start">{"⏮"}</button>
+                    <button class="menu--pass rounded-lg" onclick={go(at.saturating_sub(1))} disabled={at == 0} aria-label="Back">{"◀"}</button>
+                    <button class="menu--pass rounded-lg" onclick={go(at + 1)} disabled={at == last} aria-label="Forward">{"▶"}</button>
+                    <button class="menu--pass rounded-lg" onclick={go(last)} disabled={at == last} aria-label="End">{"⏭"}</button>
+                </div>
+                if !current.done() {
+                    <span class="text-sm">{format!("Reviewing position {} of {}…", current.reviewed() + 1, last + 1)}</span>
+                }
+                {render_verdict(current, at)}
+                <ol class="history--list">
+                    {current.steps().iter().enumerate().map(|(i, s)| {
+                        let classes = classes!("history--entry", (at == i + 1).then_some("history--entry--active"));
+                        let mark = current.verdict(i).map(|verdict| format!(" {}", verdict.symbol())).unwrap_or_default();
+                        html! {
+                            <li class={classes} onclick={go(i + 1)}>{format!("{}{}", label(game, s), mark)}</li>
+                        }
+                    }).collect::<Html>()}
+                </ol>
+                <button class="menu--pass rounded-lg" onclick={close}>{"Back to game"}</button>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,132 @@
+use chess::{Color, Piece, Square};
+
+use super::{UIBoard, View};
+use crate::game::history::Event;
+use crate::game::{Game, Kind, Spell};
+
+/// how long the css animations in index.scss run
+pub const ANIMATION_MS: f64 = 400.0;
+
+/// A visual transition between two consecutive states of the board
+#[derive(Clone, Debug)]
+pub enum Animation {
+    /// a piece slid from one cell to another, `offset` is in cells relative to its new square
+    Slide { to: Square, offset: (i32, i32) },
+    /// a piece left the board
+    Capture { square: Square, piece: Piece, color: Color },
+    /// a spell was taken off its square
+    Pickup { square: Square, kind: Kind<dyn Spell> },
+    /// a spell took effect on a square
+    Effect { square: Square, class: &'static str },
+}
+
+impl Animation {
+    pub fn square(&self) -> Square {
+        match self {
+            Animation::Slide { to, .. } => *to,
+            Animation::Capture { square, .. } => *square,
+            Animation::Pickup { square, .. } => *square,
+            Animation::Effect { square, .. } => *square,
+        }
+    }
+}
+
+fn piece_at(board: &UIBoard, square: Square) -> Option<(Piece, Color)> {
+    Some((board.board().piece_on(square)?, board.board().color_on(square)?))
+}
+
+/// pieces and spells that moved, vanished or got picked up between `prev` and `next`
+pub fn diff(prev: &UIBoard, next: &UIBoard, view: &View) -> Vec<Animation> {
+    let mut vacated = Vec::new();
+    let mut arrived = Vec::new();
+    let mut pickups = Vec::new();
+    for (file, rank) in view.cells() {
+        let square = Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file));
+        let (before, after) = (piece_at(prev, square), piece_at(next, square));
+        if before != after {
+            if let Some(piece) = before {
+                vacated.push((square, piece));
+            }
+            if let Some(piece) = after {
+                arrived.push((square, piece));
+            }
+        }
+        match (prev.kind_on(square), next.kind_on(square)) {
+            (Kind::None, _) | (_, Kind::Transparent(_) | Kind::Opaque(_)) => {},
+            (kind, Kind::None) => pickups.push(Animation::Pickup { square, kind }),
+        }
+    }
+
+    let mut animations = Vec::new();
+    for (to, piece) in arrived {
+        if let Some(i) = vacated.iter().position(|(_, p)| *p == piece) {
+            let (from, _) = vacated.remove(i);
+            let (from_col, from_row) = view.cell_of(from.get_file().to_index(), from.get_rank().to_index());
+            let (to_col, to_row) = view.cell_of(to.get_file().to_index(), to.get_rank().to_index());
+            let offset = (from_col as i32 - to_col as i32, from_row as i32 - to_row as i32);
+            animations.push(Animation::Slide { to, offset });
+        }
+    }
+    animations.extend(vacated.into_iter().map(|(square, (piece, color))| {
+        Animation::Capture { square, piece, color }
+    }));
+    animations.extend(pickups);
+    animations
+}
+
+/// spells that took effect between `prev` and `next`
+pub fn effects(prev: &Game, next: &Game) -> Vec<Animation> {
+    next.history()
+        .iter()
+        .skip(prev.history().len())
+        .filter_map(|entry| match &entry.event {
+            Event::Effect { spell, square: Some(square) } => spell.effect_class().map(|class| Animation::Effect { square: *square, class }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::moves::play;
+    use crate::game::testing::{opening, square};
+    use crate::game::Inventory;
+
+    #[test]
+    fn moves_slide_and_pick_up_spells() {
+        let game = Game::new();
+        let next = play(&game, square("d2"), square("d4")).unwrap();
+        let animations = diff(game.board(), next.board(), &game.view());
+        assert!(matches!(animations[..], [Animation::Slide { offset: (0, 2), .. }, Animation::Pickup { .. }]));
+        assert!(animations.iter().all(|animation| animation.square() == square("d4")));
+        // seen from black's side the piece comes from the other direction
+        let flipped = View::new((8, 8), Color::Black);
+        assert!(matches!(diff(game.board(), next.board(), &flipped)[0], Animation::Slide { offset: (0, -2), .. }));
+    }
+
+    #[test]
+    fn captured_pieces_vanish() {
+        let game = Game::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let next = play(&game, square("e4"), square("d5")).unwrap();
+        let animations = diff(game.board(), next.board(), &game.view());
+        assert!(matches!(animations[..], [Animation::Slide { offset: (1, 1), .. }, Animation::Capture { .. }]));
+        assert_eq!(animations[1].square(), square("d5"));
+    }
+
+    #[test]
+    fn unchanged_boards_dont_animate() {
+        let game = opening();
+        assert!(diff(game.board(), game.board(), &game.view()).is_empty());
+        assert!(effects(&game, &game).is_empty());
+    }
+
+    #[test]
+    fn spells_going_off_show_their_effect() {
+        let game = Game::new().with_inventories([Inventory::test(), Inventory::default()]);
+        let warrior = game.spells()[0].clone();
+        let next = game.cast_spell(warrior, square("e7")).unwrap().resolve();
+        assert!(matches!(effects(&game, &next)[..], [Animation::Effect { .. }]));
+        assert_eq!(effects(&game, &next)[0].square(), square("e7"));
+    }
+}
@@ -1,3 +1,6 @@
 pub mod render;
+pub mod view;
+pub mod animate;
 
-pub use render::*;
\ No newline at end of file
+pub use render::*;
+pub use view::*;
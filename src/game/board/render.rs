@@ -1,15 +1,85 @@
 use std::sync::Arc;
 
 use spell::Kind;
-use spell::{JihadiWarrior, Spell};
+use spell::{Dispel, JihadiWarrior, Reflect, Shield, Spell};
 use yew::prelude::*;
-use chess::{Board, Color, Piece, Square};
-use crate::game::moves::make_move;
+use chess::{Board, BoardBuilder, Color, Piece, Square};
+use crate::game::moves::{legal_targets, make_move};
 use crate::game::spell;
-use crate::game::Game;
+use crate::game::board::{file_name, square_name, View};
+use crate::game::board::animate::Animation;
+use crate::game::{ai, keys, theme, Game};
+use crate::game::keys::Focus;
+use crate::game::touch::{self, Drag, Payload};
 use log::info;
 
-fn render_square(sq: &UISquare) -> Html {
+/// How the board is dressed beyond the position itself
+pub struct Looks<'a> {
+    pub theme: &'a theme::Theme,
+    /// the action the last hint suggested
+    pub suggestion: Option<&'a ai::Hint>,
+    pub animations: &'a [Animation],
+}
+
+/// What a legal destination of the lifted piece would do
+#[derive(Clone, Copy, PartialEq)]
+enum Hint {
+    Move,
+    Capture,
+    Spell,
+}
+
+/// Per-square overlays that depend on the game rather than the board
+struct Marks<'a> {
+    trapped: bool,
+    selected: bool,
+    /// part of the action the last hint suggested
+    suggested: bool,
+    hint: Option<Hint>,
+    /// the square keyboard focus rests on, the only one reachable with tab
+    focused: bool,
+    animations: Vec<&'a Animation>,
+}
+
+/// Event handlers attached to a single square
+#[derive(Default)]
+struct Handlers {
+    onclick: Callback<MouseEvent>,
+    ondragstart: Callback<DragEvent>,
+    onmouseenter: Callback<MouseEvent>,
+    onmouseleave: Callback<MouseEvent>,
+    oncontextmenu: Callback<MouseEvent>,
+}
+
+/// what a screen reader says for a square, e.g. "e4, white knight, Shield, capture"
+fn aria_label(sq: &UISquare, marks: &Marks) -> String {
+    let mut parts = vec![sq.name()];
+    parts.push(match &sq.piece {
+        Some(piece) => format!("{:?} {:?}", piece.color, piece.piece).to_lowercase(),
+        None => "empty".to_owned(),
+    });
+    match &sq.spell {
+        Kind::Transparent(spell) => parts.push(spell.name().to_owned()),
+        Kind::Opaque(_) => parts.push("unknown spell".to_owned()),
+        Kind::None => {},
+    }
+    if marks.trapped {
+        parts.push("your trap".to_owned());
+    }
+    if marks.suggested {
+        parts.push("suggested".to_owned());
+    }
+    match marks.hint {
+        Some(Hint::Move) => parts.push("legal move".to_owned()),
+        Some(Hint::Capture) => parts.push("capture".to_owned()),
+        Some(Hint::Spell) => parts.push("picks up spell".to_owned()),
+        None => {},
+    }
+    parts.join(", ")
+}
+
+fn render_square(sq: &UISquare, view: &View, theme: &theme::Theme, marks: &Marks, handlers: Handlers, drag: Option<&UseStateHandle<Option<Drag>>>) -> Html {
+    let Handlers { onclick, ondragstart, onmouseenter, onmouseleave, oncontextmenu } = handlers;
 
     let color = match sq.color {
         Color::White => "white",
@@ -18,28 +88,87 @@ fn render_square(sq: &UISquare) -> Html {
 
     let inner = match sq.piece.clone() {
         Some(piece) => {
-            let piece_color = match piece.color {
-                Color::White => "white",
-                Color::Black => "black",
+            let src = theme.piece_src(piece.piece, piece.color);
+            let onpointerdown = match drag {
+                Some(drag) => touch::start(drag, Payload::Piece { from: sq.square(), src: src.clone() }),
+                None => Callback::noop(),
             };
             html! {
-                <img id={sq.str_id()} src={format!("/assets/pieces/{}/{}.svg", piece_color, piece.to_string().to_lowercase())} alt={format!("{:?}", piece.to_string())} />
+                <img id={format!("piece-{}", sq.name())} {src} {onpointerdown} {ondragstart} alt={format!("{:?}", piece.to_string())} />
             }
         },
         None => html! {},
     };
 
+    let classes = classes!("square", format!("square--{}", color), marks.selected.then_some("square--selected"), marks.suggested.then_some("square--suggested"));
+    let hint = match marks.hint {
+        Some(Hint::Move) => html! { <div class="hint hint--move"></div> },
+        Some(Hint::Capture) => html! { <div class="hint hint--capture"></div> },
+        Some(Hint::Spell) => html! { <div class="hint hint--spell"></div> },
+        None => html! {},
+    };
+    // rank numbers run down the left edge, file letters along the bottom
+    let (col, row) = view.cell_of(sq.id[0] as usize, sq.id[1] as usize);
+    let rank_label = (col == 0).then(|| html! { <span class="label label--rank">{sq.id[1] + 1}</span> });
+    let file_label = (row == view.dims().1 - 1).then(|| html! { <span class="label label--file">{file_name(sq.id[0] as usize)}</span> });
+    let mut piece_classes = classes!("piece");
+    let mut piece_style = String::new();
+    let overlays = marks.animations.iter().map(|a| match a {
+        Animation::Slide { offset, .. } => {
+            piece_classes.push("anim-slide");
+            piece_style = format!("--dx: {}; --dy: {};", offset.0, offset.1);
+            html! {}
+        },
+        Animation::Capture { piece, color, .. } => html! {
+            <div class="piece anim-capture">
+                <img src={theme.piece_src(*piece, *color)} />
+            </div>
+        },
+        Animation::Pickup { kind, .. } => html! {
+            <div class="anim-pickup">
+                {spell::render(kind)}
+            </div>
+        },
+        Animation::Effect { class, .. } => html! {
+            <div class={*class}></div>
+        },
+    }).collect::<Html>();
     html! {
-        <div class={"square square--".to_owned() + color} style={format!{"width: {}%; height: {}%;", sq.width, sq.height}}>
-            <div class="piece">
+        <div id={sq.name()} title={sq.name()} role="gridcell" aria-label={aria_label(sq, marks)} aria-selected={marks.selected.to_string()} tabindex={if marks.focused { "0" } else { "-1" }} class={classes} {onclick} {onmouseenter} {onmouseleave} {oncontextmenu} style={format!{"width: {}%; height: {}%;", sq.width, sq.height}}>
+            {rank_label}
+            {file_label}
+            <div class={piece_classes} style={piece_style}>
                 {inner}
             </div>
+            {overlays}
             {spell::render(&sq.spell)}
+            if marks.trapped {
+                <div class="trap"></div>
+            }
+            {hint}
         </div>
     }
 }
 
-#[derive(Clone)]
+/// Error Type for a piece that can't be taken off the board
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RemoveError {
+    /// kings never leave the board
+    King(Square),
+    /// the side not to move would be left in check, which no position allows
+    ExposesKing(Square),
+}
+
+impl std::fmt::Display for RemoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RemoveError::King(square) => write!(f, "The king on {} can't be removed", square_name(*square)),
+            RemoveError::ExposesKing(square) => write!(f, "Removing the piece on {} would leave the king to move into capture", square_name(*square)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct UIBoard {
     dims: (usize, usize),
     board: Board,
@@ -52,9 +181,9 @@ struct UIPiece {
     color: Color,
 }
 
-impl ToString for UIPiece {
-    fn to_string(&self) -> String {
-        self.piece.to_string(self.color)
+impl std::fmt::Display for UIPiece {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.piece.to_string(self.color))
     }
 }
 
@@ -76,30 +205,31 @@ impl PartialEq for UISquare {
 
 impl UISquare {
     fn gen_square(file: usize, rank: usize, piece: Option<Piece>, color: Option<Color>, spell: spell::Kind<dyn Spell>, size: (f64, f64)) -> UISquare {
-        let piece = match piece {
-            Some(piece) => Some(UIPiece{ piece, color: color.unwrap() }),
-            None => None,
-        };
+        let piece = piece.map(|piece| UIPiece{ piece, color: color.unwrap() });
         match (file + rank) % 2 {
-            0 => Self{ piece, color: Color::White, spell, width: size.0, height: size.1, id: [file as u8, rank as u8]},
+            1 => Self{ piece, color: Color::White, spell, width: size.0, height: size.1, id: [file as u8, rank as u8]},
             _ => Self{ piece, color: Color::Black, spell, width: size.0, height: size.1, id: [file as u8, rank as u8]},
         }
     }
-    fn str_id(&self) -> String {
-        format!("{}{}", self.id[0], self.id[1])
+    fn square(&self) -> Square {
+        Square::make_square(chess::Rank::from_index(self.id[1] as usize), chess::File::from_index(self.id[0] as usize))
+    }
+    fn name(&self) -> String {
+        square_name(self.square())
     }
 }
 
 impl Default for UIBoard {
     fn default() -> Self {
         let mut spells: Vec<Kind<dyn Spell>> = Vec::with_capacity(64);
-        (0..64).into_iter().for_each(|i| {
-            if i == 4 {
-                spells.push(Kind::Opaque(Arc::new(JihadiWarrior::default())))
-            } else if i == 20 {
-                spells.push(Kind::Transparent(Arc::new(JihadiWarrior::default())));
-            } else {
-                spells.push(Kind::None);
+        (0..64).for_each(|i| {
+            match i {
+                4 => spells.push(Kind::Opaque(Arc::new(JihadiWarrior::default()))),
+                20 => spells.push(Kind::Transparent(Arc::new(JihadiWarrior::default()))),
+                27 => spells.push(Kind::Transparent(Arc::new(Reflect))),
+                43 => spells.push(Kind::Opaque(Arc::new(Dispel))),
+                59 => spells.push(Kind::Transparent(Arc::new(Shield))),
+                _ => spells.push(Kind::None),
             }
         });
         Self {
@@ -114,7 +244,7 @@ impl UIBoard {
     pub fn new(board: Board, dims: (usize, usize), spells: Option<Arc<Vec<spell::Kind<dyn Spell>>>>) -> Self {
         let spells = spells.unwrap_or({
             let mut default = Vec::with_capacity(dims.0 * dims.1);
-            (0..dims.0 * dims.1).into_iter().for_each(|i| {
+            (0..dims.0 * dims.1).for_each(|_| {
                 default.push(Kind::None);
             });
             Arc::new(default)
@@ -122,17 +252,17 @@ impl UIBoard {
         Self {
             dims,
             spells,
-            board: board.clone(),
+            board,
         }
     }
     fn gen_squares(&self) -> Vec<UISquare> {
         let width = (1.0 / self.dims.0 as f64) * 100.0;
         let height = (1.0 / self.dims.1 as f64) * 100.0;
-        (0..self.dims.1).into_iter().map(|rank| {
-            (0..self.dims.0).into_iter().map(move |file| {
+        (0..self.dims.1).flat_map(|rank| {
+            (0..self.dims.0).map(move |file| {
                 let square = Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file));
                 let mut spell = Kind::None;
-                match &self.spells.clone()[square.get_file().to_index() * self.dims().0 + square.get_rank().to_index()] {
+                match &self.spells[self.spell_index(square)] {
                     Kind::Transparent(s) => spell = Kind::Transparent(s.clone()),
                     Kind::Opaque(s) => spell = Kind::Opaque(s.clone()),
                     Kind::None => ()
@@ -142,7 +272,7 @@ impl UIBoard {
                 let color = self.board.color_on(square);
                 UISquare::gen_square(file, rank, piece, color, spell, (width, height))
             })
-        }).flat_map(|x| x).collect()
+        }).collect()
     }
 
     pub fn board(&self) -> &Board {
@@ -162,48 +292,216 @@ impl UIBoard {
         Self::new(b_, self.dims, Some(self.spells.clone()))
     }
 
+    /// spells on every square, file after file, see `spell_square`
+    pub fn spells(&self) -> &[Kind<dyn Spell>] {
+        &self.spells
+    }
+
+    /// where the spell on `square` is kept in `spells`
+    fn spell_index(&self, square: Square) -> usize {
+        square.get_file().to_index() * self.dims.1 + square.get_rank().to_index()
+    }
+
+    /// the square the spell at `index` in `spells` lies on
+    pub fn spell_square(&self, index: usize) -> Square {
+        Square::make_square(chess::Rank::from_index(index % self.dims.1), chess::File::from_index(index / self.dims.1))
+    }
+
     pub fn dims(&self) -> (usize, usize) {
         self.dims
     }
 
+    /// the spell lying on `square`, together with whether it is face up
+    pub fn kind_on(&self, square: chess::Square) -> Kind<dyn Spell> {
+        self.spells[self.spell_index(square)].clone()
+    }
+
     pub fn spell_on(&self, square: chess::Square) -> Option<Arc<dyn Spell>> {
-        let s = &self.spells[square.get_file().to_index() * self.dims().0 + square.get_rank().to_index()];
-        info!("spell on {:?}:{:?}", square, s);
+        let s = self.kind_on(square);
+        info!("spell on {}:{:?}", square_name(square), s);
         match s {
             Kind::None => None,
-            Kind::Opaque(s) => Some(s.clone()),
-            Kind::Transparent(s) => Some(s.clone())
+            Kind::Opaque(s) => Some(s),
+            Kind::Transparent(s) => Some(s)
         }
     }
 
-    pub fn remove_piece(&self, square: Square) -> UIBoard {
-        let board = self.board().clone();
-        let cleared = board.clear_square(square);
-        match cleared {
-            Some(board) => Self::new(board, self.dims, Some(self.spells.clone())),
-            None => self.clone(),
+    /// the board without the spell lying on `square`
+    pub fn take_spell(&self, square: Square) -> UIBoard {
+        let mut spells = self.spells.as_ref().clone();
+        spells[self.spell_index(square)] = Kind::None;
+        Self::new(self.board, self.dims, Some(Arc::new(spells)))
+    }
+
+    /// the board without the piece on `square`, along with the castle rights and en passant
+    /// square that depended on it
+    pub fn remove_piece(&self, square: Square) -> Result<UIBoard, RemoveError> {
+        if self.board.piece_on(square) == Some(Piece::King) {
+            return Err(RemoveError::King(square));
+        }
+        let mut builder = BoardBuilder::from(self.board());
+        builder.clear_square(square);
+        for color in chess::ALL_COLORS {
+            let rights = builder.get_castle_rights(color).remove(chess::CastleRights::square_to_castle_rights(color, square));
+            builder.castle_rights(color, rights);
+        }
+        if builder.get_en_passant() == Some(square) {
+            builder.en_passant(None);
+        }
+        match Board::try_from(&builder) {
+            Ok(board) => Ok(Self::new(board, self.dims, Some(self.spells.clone()))),
+            Err(_) => Err(RemoveError::ExposesKing(square)),
         }
     }
 }
 
-fn wrapped_move(state: UseStateHandle<Game>) -> Callback<DragEvent, ()> {
+fn wrapped_move(state: UseStateHandle<Game>, selected: UseStateHandle<Option<Square>>) -> Callback<DragEvent, ()> {
     let ondrag = Callback::from(
         move |e| {
             let game = state.clone();
             let _board = make_move(&game, e);
             info!("{:?}", game.raw_board().side_to_move());
+            selected.set(None);
             state.set(_board)
         });
     ondrag
 }
 
-pub fn render(state: UseStateHandle<Game>) -> Html {
+/// the board to play on as `viewer` sees it, `drag` is `None` while it takes no input
+pub fn render(state: UseStateHandle<Game>, selected: UseStateHandle<Option<Square>>, drag: Option<&UseStateHandle<Option<Drag>>>, detail: &UseStateHandle<Option<spell::Detail>>, focus: &UseStateHandle<Focus>, looks: Looks, viewer: Color) -> Html {
+    let Looks { theme, suggestion, animations } = looks;
     // let whites = game.current_position().color_combined(chess::Color::White);
     // let blacks = game.current_position().color_combined(chess::Color::Black);
     let game = state.clone();
+    // traps stay hidden from everyone but the side that armed them
+    let traps = game.traps(viewer);
+    let view = game.view();
+    let dims = game.board().dims();
+    let squares = game.board().gen_squares();
+    let lifted = drag.and_then(|d| d.as_ref()).and_then(|d| d.piece_from()).or(*selected);
+    let targets = lifted.map(|from| legal_targets(&game, from)).unwrap_or_default();
+    let suggested = suggestion.filter(|hint| hint.fits(&game)).map(|hint| hint.squares()).unwrap_or_default();
+    let cursor = focus.cursor.unwrap_or_else(|| view.corner());
+    let input = drag.is_some();
+    let (onkeydown, ondragend) = match input {
+        true => (keys::navigate(&state, &selected, focus), wrapped_move(state.clone(), selected.clone())),
+        false => (Callback::noop(), Callback::noop()),
+    };
     html! {
-        <div id="board" class="board" ondragend={wrapped_move(state)}>
-            { game.board().gen_squares().iter().rev().map(|s| render_square(s)).collect::<Html>() }
+        <div id="board" class="board" role="grid" aria-label="Chessboard" aria-readonly={(!input).to_string()} {onkeydown} {ondragend}>
+            { view.cells().chunks(dims.0).map(|row| html! {
+                <div class="board--row" role="row">
+                { row.iter().map(|&(file, rank)| {
+                let s = &squares[rank * dims.0 + file];
+                let square = s.square();
+                let hint = targets.contains(&square).then(|| {
+                    if game.board().spell_on(square).is_some() {
+                        Hint::Spell
+                    } else if game.raw_board().piece_on(square).is_some() {
+                        Hint::Capture
+                    } else {
+                        Hint::Move
+                    }
+                });
+                let marks = Marks {
+                    trapped: traps.contains(&square),
+                    selected: lifted == Some(square),
+                    suggested: suggested.contains(&square),
+                    hint,
+                    focused: cursor == square,
+                    animations: animations.iter().filter(|a| a.square() == square).collect(),
+                };
+                let (onclick, ondragstart) = match input {
+                    true => {
+                        let (state, selected, focus) = (state.clone(), selected.clone(), focus.clone());
+                        let lift = selected.clone();
+                        (Callback::from(move |_| keys::activate(&state, &selected, &focus, square)), Callback::from(move |_| lift.set(Some(square))))
+                    },
+                    false => (Callback::noop(), Callback::noop()),
+                };
+                // opaque spells stay a mystery, armed traps are only described to their owner
+                let described = match game.board().kind_on(square) {
+                    Kind::Transparent(spell) => Some((spell, None)),
+                    _ => game.trap_on(viewer, square).map(|spell| (spell, game.trap_remaining(viewer, square))),
+                };
+                let handlers = match described {
+                    Some((spell, remaining)) => {
+                        let (onmouseenter, onmouseleave) = spell::hover_detail(detail, spell.clone(), remaining);
+                        let oncontextmenu = spell::press_detail(detail, spell, remaining);
+                        Handlers { onclick, ondragstart, onmouseenter, onmouseleave, oncontextmenu }
+                    },
+                    None => Handlers { onclick, ondragstart, ..Default::default() },
+                };
+                render_square(s, &view, theme, &marks, handlers, drag)
+                }).collect::<Html>() }
+                </div>
+            }).collect::<Html>() }
         </div>
     }
-}
\ No newline at end of file
+}
+
+/// a read-only board showing a past position of `game`
+pub fn render_past(game: &Game, past: &UIBoard, theme: &theme::Theme) -> Html {
+    let view = game.view();
+    let dims = past.dims();
+    let squares = past.gen_squares();
+    html! {
+        <div id="board" class="board board--past" role="grid" aria-label="Past position" aria-readonly="true">
+            { view.cells().chunks(dims.0).map(|row| html! {
+                <div class="board--row" role="row">
+                { row.iter().map(|&(file, rank)| {
+                    let marks = Marks { trapped: false, selected: false, suggested: false, hint: None, focused: false, animations: Vec::new() };
+                    render_square(&squares[rank * dims.0 + file], &view, theme, &marks, Handlers::default(), None)
+                }).collect::<Html>() }
+                </div>
+            }).collect::<Html>() }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::square;
+
+    fn board(fen: &str) -> UIBoard {
+        UIBoard::new(fen.parse().unwrap(), (8, 8), None)
+    }
+
+    #[test]
+    fn removing_a_rook_drops_its_castle_rights() {
+        let cleared = UIBoard::new(Board::default(), (8, 8), None).remove_piece(square("a1")).unwrap();
+        assert_eq!(cleared.piece_on(square("a1")), None);
+        assert_eq!(cleared.board().castle_rights(Color::White), chess::CastleRights::KingSide);
+        assert_eq!(cleared.board().castle_rights(Color::Black), chess::CastleRights::Both);
+    }
+
+    #[test]
+    fn kings_stay() {
+        assert_eq!(Board::default().piece_on(square("e1")), Some(Piece::King));
+        assert_eq!(UIBoard::new(Board::default(), (8, 8), None).remove_piece(square("e1")).unwrap_err(), RemoveError::King(square("e1")));
+    }
+
+    #[test]
+    fn the_side_not_to_move_cant_be_left_in_check() {
+        let pinned = board("4k3/4b3/8/8/8/8/8/4RK2 w - - 0 1");
+        assert_eq!(pinned.remove_piece(square("e7")).unwrap_err(), RemoveError::ExposesKing(square("e7")));
+        let game = Game::from_fen("4k3/4b3/8/8/8/8/8/4RK2 w - - 0 1").unwrap();
+        // the bishop comes off too once the rook pinning it is gone
+        let cleared = game.clear_squares(&[square("e7"), square("e1")]);
+        assert_eq!(cleared.raw_board().combined().popcnt(), 2);
+    }
+
+    #[test]
+    fn spells_are_found_on_boards_of_any_size() {
+        let mut spells: Vec<Kind<dyn Spell>> = (0..6 * 4).map(|_| Kind::None).collect();
+        spells[23] = Kind::Transparent(Arc::new(Shield));
+        let narrow = UIBoard::new(Board::default(), (6, 4), Some(Arc::new(spells)));
+        assert_eq!(narrow.spell_square(23), square("f4"));
+        assert!(matches!(narrow.kind_on(square("f4")), Kind::Transparent(_)));
+        assert!(matches!(narrow.take_spell(square("f4")).kind_on(square("f4")), Kind::None));
+        for index in 0..narrow.spells().len() {
+            assert_eq!(narrow.spell_index(narrow.spell_square(index)), index);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use chess::{Color, Square};
+
+/// letter of a file, `a` for the first one
+pub fn file_name(file: usize) -> char {
+    (b'a' + file as u8) as char
+}
+
+/// algebraic name of a square, e.g. `e4`
+pub fn square_name(square: Square) -> String {
+    square.to_string()
+}
+
+/// letter of a side in FEN and the spell commands of PGN, `w` or `b`
+pub fn color_char(color: Color) -> char {
+    match color {
+        Color::White => 'w',
+        Color::Black => 'b',
+    }
+}
+
+/// parses an algebraic square name like `e4`, nothing more
+pub fn parse_square(name: &str) -> Option<Square> {
+    // chess ignores anything after the first two characters
+    (name.len() == 2).then(|| name.parse().ok()).flatten()
+}
+
+/// Maps board coordinates to screen cells and back, for either side at the bottom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    dims: (usize, usize),
+    bottom: Color,
+}
+
+impl View {
+    pub fn new(dims: (usize, usize), bottom: Color) -> Self {
+        Self { dims, bottom }
+    }
+
+    pub fn dims(&self) -> (usize, usize) {
+        self.dims
+    }
+
+    /// (file, rank) -> (column from the left, row from the top)
+    pub fn cell_of(&self, file: usize, rank: usize) -> (usize, usize) {
+        match self.bottom {
+            Color::White => (file, self.dims.1 - 1 - rank),
+            Color::Black => (self.dims.0 - 1 - file, rank),
+        }
+    }
+
+    /// (column from the left, row from the top) -> (file, rank)
+    pub fn coords_of(&self, col: usize, row: usize) -> (usize, usize) {
+        // the transform is its own inverse
+        self.cell_of(col, row)
+    }
+
+    /// the square `cols` to the right and `rows` down from `square` on screen, `None` past the edge
+    pub fn step(&self, square: Square, cols: isize, rows: isize) -> Option<Square> {
+        let (col, row) = self.cell_of(square.get_file().to_index(), square.get_rank().to_index());
+        let col = col.checked_add_signed(cols).filter(|c| *c < self.dims.0)?;
+        let row = row.checked_add_signed(rows).filter(|r| *r < self.dims.1)?;
+        let (file, rank) = self.coords_of(col, row);
+        Some(Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file)))
+    }
+
+    /// the square in the bottom left corner, where keyboard focus starts
+    pub fn corner(&self) -> Square {
+        let (file, rank) = self.coords_of(0, self.dims.1 - 1);
+        Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file))
+    }
+
+    /// (file, rank) of every cell, in reading order from the top left
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        (0..self.dims.1)
+            .flat_map(|row| (0..self.dims.0).map(move |col| (col, row)))
+            .map(|(col, row)| self.coords_of(col, row))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_names_round_trip() {
+        for square in chess::ALL_SQUARES {
+            assert_eq!(parse_square(&square_name(square)), Some(square));
+        }
+        for name in ["e+4", "e04", "e4x", "i4", "e9", "E4", ""] {
+            assert_eq!(parse_square(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn black_sees_the_board_turned_around() {
+        let view = View::new((8, 8), Color::Black);
+        assert_eq!(view.cell_of(0, 0), (7, 0));
+        assert_eq!(view.cell_of(7, 7), (0, 7));
+        assert_eq!(view.corner(), Square::H8);
+        assert_eq!(view.cells()[0], (7, 0));
+    }
+
+    #[test]
+    fn cells_map_back_to_coordinates() {
+        for dims in [(8, 8), (6, 4), (3, 7)] {
+            for bottom in [Color::White, Color::Black] {
+                let view = View::new(dims, bottom);
+                for file in 0..dims.0 {
+                    for rank in 0..dims.1 {
+                        let (col, row) = view.cell_of(file, rank);
+                        assert!(col < dims.0 && row < dims.1);
+                        assert_eq!(view.coords_of(col, row), (file, rank));
+                    }
+                }
+                assert_eq!(view.cells().len(), dims.0 * dims.1);
+            }
+        }
+    }
+
+    #[test]
+    fn steps_stop_at_the_edges_instead_of_wrapping() {
+        let view = View::new((8, 8), Color::White);
+        assert_eq!(view.step(Square::A1, -1, 0), None);
+        assert_eq!(view.step(Square::A1, 0, 1), None);
+        assert_eq!(view.step(Square::H4, 1, 0), None);
+        assert_eq!(view.step(Square::A8, 0, -1), None);
+        assert_eq!(view.step(Square::A1, 1, -1), Some(Square::B2));
+        // turned around, up on screen is down the ranks
+        let black = View::new((8, 8), Color::Black);
+        assert_eq!(black.step(Square::H8, 0, -1), Some(Square::H7));
+        assert_eq!(black.step(Square::A1, 1, 0), None);
+        let small = View::new((4, 2), Color::White);
+        assert_eq!(small.step(Square::D1, 1, 0), None);
+        assert_eq!(small.step(Square::D2, 0, -1), None);
+    }
+}
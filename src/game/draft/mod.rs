@@ -0,0 +1,170 @@
+pub mod render;
+
+use std::sync::Arc;
+
+use chess::Color;
+
+pub use render::*;
+use super::{catalogue, Inventory, Spell};
+
+/// Error Type for a draft action that isn't allowed right now
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DraftError {
+    Finished,
+    NotInPool,
+}
+
+impl std::fmt::Display for DraftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DraftError::Finished => write!(f, "The draft is over"),
+            DraftError::NotInPool => write!(f, "That spell is not in the pool"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Ban,
+    Pick,
+    Done,
+}
+
+/// Pre-game draft where both sides alternately ban and pick spells from a random pool
+#[derive(Clone)]
+pub struct Draft {
+    pool: Vec<Arc<dyn Spell>>,
+    banned: Vec<Arc<dyn Spell>>,
+    picks: [Inventory; 2],
+    bans_per_side: usize,
+    picks_per_side: usize,
+    actions: usize,
+}
+
+impl Draft {
+    pub fn new(seed: u64) -> Self {
+        Self::with_sizes(seed, 8, 1, 3)
+    }
+
+    pub fn with_sizes(seed: u64, pool_size: usize, bans_per_side: usize, picks_per_side: usize) -> Self {
+        let spells = catalogue();
+        let mut state = seed | 1;
+        let pool = (0..pool_size).map(|_| {
+            // xorshift64, good enough to shuffle a handful of spells
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            spells[(state % spells.len() as u64) as usize].clone()
+        }).collect();
+        Self {
+            pool,
+            banned: Vec::new(),
+            picks: Default::default(),
+            bans_per_side,
+            picks_per_side,
+            actions: 0,
+        }
+    }
+
+    /// over as soon as the pool runs dry, even during the bans
+    pub fn phase(&self) -> Phase {
+        let bans = 2 * self.bans_per_side;
+        if self.pool.is_empty() {
+            Phase::Done
+        } else if self.actions < bans {
+            Phase::Ban
+        } else if self.actions < bans + 2 * self.picks_per_side {
+            Phase::Pick
+        } else {
+            Phase::Done
+        }
+    }
+
+    /// white opens both the ban and the pick round
+    pub fn side_to_act(&self) -> Color {
+        match self.actions % 2 {
+            0 => Color::White,
+            _ => Color::Black,
+        }
+    }
+
+    pub fn pool(&self) -> &[Arc<dyn Spell>] {
+        &self.pool
+    }
+
+    pub fn banned(&self) -> &[Arc<dyn Spell>] {
+        &self.banned
+    }
+
+    pub fn picks(&self, color: Color) -> &Inventory {
+        &self.picks[color.to_index()]
+    }
+
+    /// bans or picks the spell at `index` in the pool, depending on the phase
+    pub fn choose(&self, index: usize) -> Result<Self, DraftError> {
+        if self.phase() == Phase::Done {
+            return Err(DraftError::Finished);
+        }
+        if index >= self.pool.len() {
+            return Err(DraftError::NotInPool);
+        }
+        let mut draft = self.clone();
+        let spell = draft.pool.remove(index);
+        if self.phase() == Phase::Ban {
+            draft.banned.push(spell);
+        } else {
+            let side = self.side_to_act().to_index();
+            draft.picks[side] = draft.picks[side].to_owned().collect_spell(spell);
+        }
+        draft.actions += 1;
+        Ok(draft)
+    }
+
+    /// the starting inventories of both sides, indexed by color
+    pub fn inventories(&self) -> [Inventory; 2] {
+        self.picks.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{JihadiWarrior, Shield};
+
+    #[test]
+    fn bans_come_before_picks() {
+        let mut draft = Draft::with_sizes(7, 10, 1, 3);
+        let mut phases = Vec::new();
+        while draft.phase() != Phase::Done {
+            phases.push((draft.phase(), draft.side_to_act()));
+            draft = draft.choose(0).unwrap();
+        }
+        assert_eq!(phases[..3], [(Phase::Ban, Color::White), (Phase::Ban, Color::Black), (Phase::Pick, Color::White)]);
+        assert_eq!(phases.len(), 8);
+        assert_eq!(draft.choose(0).err(), Some(DraftError::Finished));
+    }
+
+    #[test]
+    fn duplicate_picks_merge() {
+        let mut draft = Draft::with_sizes(7, 0, 0, 2);
+        draft.pool = vec![Arc::new(JihadiWarrior::default()), Arc::new(Shield), Arc::new(JihadiWarrior::default())];
+        for _ in 0..3 {
+            draft = draft.choose(0).unwrap();
+        }
+        let white = draft.picks(Color::White).spells();
+        assert_eq!(white.len(), 1);
+        assert_eq!(white[0].level(), 2);
+        assert_eq!(draft.picks(Color::Black).spells()[0].identifier(), Shield.identifier());
+    }
+
+    #[test]
+    fn empty_pools_end_the_draft() {
+        let draft = Draft::with_sizes(7, 0, 1, 3);
+        assert_eq!(draft.phase(), Phase::Done);
+        assert_eq!(draft.choose(0).err(), Some(DraftError::Finished));
+
+        // both spells are banned, nothing is left to pick
+        let draft = Draft::with_sizes(7, 2, 1, 3).choose(0).unwrap().choose(0).unwrap();
+        assert_eq!(draft.phase(), Phase::Done);
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use chess::Color;
+use log::info;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use super::{Draft, Phase};
+use crate::game::ai::{self, Computer};
+use crate::game::{Game, Spell};
+
+fn render_spell(spell: &Arc<dyn Spell>, classes: &'static str, onclick: Callback<MouseEvent>) -> Html {
+    html! {
+        <div class={classes} title={spell.description()} {onclick}>
+            <div class="spell--icon">
+                <div class={spell.class_list()}>
+                </div>
+            </div>
+            <span class="text-base">{spell.name()}</span>
+        </div>
+    }
+}
+
+fn render_picks(draft: &Draft, color: Color) -> Html {
+    html! {
+        <div class="draft--picks p-4 rounded-lg">
+            <span class="text-sm">{format!("{:?}", color)}</span>
+            {draft.picks(color).spells().iter().map(|spell| {
+                render_spell(spell, "spell--wrapper p-4 shadow-xl rounded-lg", Callback::noop())
+            }).collect::<Html>()}
+        </div>
+    }
+}
+
+/// the game the draft leads to, seen from the human's side when playing the computer
+fn start(draft: &Draft, computer: Option<Computer>) -> Game {
+    let game = Game::new().with_inventories(draft.inventories());
+    match computer {
+        Some(computer) if computer.side == game.orientation() => game.flip_board(),
+        _ => game,
+    }
+}
+
+/// lets the computer ban and pick until it's the human's turn, then moves on to the game once done
+fn advance(draft: &UseStateHandle<Option<Draft>>, game: &UseStateHandle<Game>, next: Draft, computer: Option<Computer>) {
+    let mut next = next;
+    while next.phase() != Phase::Done && computer.is_some_and(|computer| computer.side == next.side_to_act()) {
+        match next.choose(ai::draft_choice(&next)) {
+            Ok(chosen) => next = chosen,
+            Err(e) => {
+                info!("{}", e);
+                break;
+            },
+        }
+    }
+    match next.phase() {
+        Phase::Done => {
+            game.set(start(&next, computer));
+            draft.set(None);
+        },
+        _ => draft.set(Some(next)),
+    }
+}
+
+/// a dropdown over `options`, `choose` getting the index picked
+fn render_select(label: &'static str, options: Vec<(&'static str, bool)>, choose: Callback<usize>) -> Html {
+    let onchange = Callback::from(move |e: Event| {
+        let select: HtmlSelectElement = e.target_unchecked_into();
+        if let Ok(index) = select.value().parse() {
+            choose.emit(index);
+        }
+    });
+    html! {
+        <label class="text-sm">
+            {label}
+            <select {onchange}>
+                { options.into_iter().enumerate().map(|(i, (name, selected))| html! {
+                    <option value={i.to_string()} {selected}>{name}</option>
+                }).collect::<Html>() }
+            </select>
+        </label>
+    }
+}
+
+/// whether and how the computer plays, set before the draft so it can draft for itself
+fn render_opponent(draft: &UseStateHandle<Option<Draft>>, game: &UseStateHandle<Game>, computer: &UseStateHandle<Option<Computer>>, current: &Draft) -> Html {
+    let set = {
+        let draft = draft.clone();
+        let game = game.clone();
+        let computer = computer.clone();
+        let current = current.clone();
+        move |next: Option<Computer>| {
+            computer.set(next);
+            advance(&draft, &game, current.clone(), next);
+        }
+    };
+    let toggle = {
+        let set = set.clone();
+        let playing = **computer;
+        Callback::from(move |_| set(match playing {
+            Some(_) => None,
+            None => Some(Computer::new(Color::Black)),
+        }))
+    };
+    let settings = match **computer {
+        Some(playing) => {
+            let side = {
+                let set = set.clone();
+                Callback::from(move |i: usize| set(Some(Computer { side: chess::ALL_COLORS[i % 2], ..playing })))
+            };
+            let level = {
+                let set = set.clone();
+                Callback::from(move |i: usize| set(Some(Computer { level: ai::Level::ALL[i % 4], ..playing })))
+            };
+            let personality = {
+                let set = set.clone();
+                Callback::from(move |i: usize| set(Some(Computer { personality: ai::Personality::ALL[i % 4], ..playing })))
+            };
+            html! {
+                <>
+                    {render_select("Plays ", chess::ALL_COLORS.iter().map(|c| (if *c == Color::White { "White" } else { "Black" }, *c == playing.side)).collect(), side)}
+                    {render_select("Level ", ai::Level::ALL.iter().map(|l| (l.name(), *l == playing.level)).collect(), level)}
+                    {render_select("Style ", ai::Personality::ALL.iter().map(|p| (p.name(), *p == playing.personality)).collect(), personality)}
+                </>
+            }
+        },
+        None => html! {},
+    };
+    html! {
+        <div class="draft--opponent">
+            <label class="text-sm">
+                <input type="checkbox" checked={computer.is_some()} onclick={toggle} />
+                {" Play against the computer"}
+            </label>
+            {settings}
+        </div>
+    }
+}
+
+pub fn render(draft: &UseStateHandle<Option<Draft>>, game: &UseStateHandle<Game>, computer: &UseStateHandle<Option<Computer>>) -> Html {
+    let current = match draft.as_ref() {
+        Some(current) => current.clone(),
+        None => return html! {},
+    };
+    let header = match current.phase() {
+        Phase::Ban => format!("{:?} bans a spell", current.side_to_act()),
+        Phase::Pick => format!("{:?} picks a spell", current.side_to_act()),
+        Phase::Done => "Draft complete".to_owned(),
+    };
+    let skip = {
+        let draft = draft.clone();
+        let game = game.clone();
+        let current = current.clone();
+        let computer = **computer;
+        Callback::from(move |_| {
+            game.set(start(&current, computer));
+            draft.set(None);
+        })
+    };
+    html! {
+        <div class="draft p-6 shadow-2xl rounded-xl">
+            {render_opponent(draft, game, computer, &current)}
+            <span class="text-base">{header}</span>
+            <div class="draft--pool">
+                {current.pool().iter().enumerate().map(|(i, spell)| {
+                    let draft = draft.clone();
+                    let game = game.clone();
+                    let current = current.clone();
+                    let computer = **computer;
+                    let onclick = Callback::from(move |_| {
+                        match current.choose(i) {
+                            Ok(next) => advance(&draft, &game, next, computer),
+                            Err(e) => info!("{}", e),
+                        }
+                    });
+                    render_spell(spell, "spell--wrapper p-4 shadow-xl rounded-lg", onclick)
+                }).collect::<Html>()}
+            </div>
+            <div class="draft--banned">
+                {current.banned().iter().map(|spell| {
+                    render_spell(spell, "spell--wrapper spell--wrapper--disabled p-4 rounded-lg", Callback::noop())
+                }).collect::<Html>()}
+            </div>
+            <div class="draft--sides">
+                {render_picks(&current, Color::White)}
+                {render_picks(&current, Color::Black)}
+            </div>
+            <button class="menu--pass rounded-lg" onclick={skip}>{"Start game"}</button>
+        </div>
+    }
+}
@@ -0,0 +1,335 @@
+//! "Magic FEN": standard FEN followed by up to six fields describing the magic.
+//!
+//! `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 w@a5,W@c5,R@d4,d@f4,S@h4 -/- - 0 -`
+//!
+//! 1. spells on squares: a spell letter, its level if above 1, `@` and the square.
+//!    Upper case spells are visible, lower case ones hidden
+//! 2. inventories, white's then black's, separated by `/`
+//! 3. active effects: armed traps as `bW@e5~3` (owner, spell, square, plies until it expires),
+//!    followed by `#w` when white's pieces are shielded from it, and casts on the stack from the
+//!    bottom up as `!wS@c6`
+//! 4. spells cast by the side to move this turn
+//! 5. cooldowns as `wW:2` (side, spell, plies until it can be cast again)
+//! 6. hints taken, white's then black's, as `1/0`. Left out while neither side took one
+//!
+//! `-` stands for an empty field. Ply counts are relative because a parsed game starts without
+//! move history. The magic fields may be left out, which gives a position without any spells.
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chess::{Board, Color, Square};
+
+use super::board::{color_char, parse_square, square_name, UIBoard};
+use super::{from_spell_token, spell_token, symbol, Cast, Game, Inventory, Kind, Spell};
+
+/// Error Type for a string that isn't valid Magic FEN
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    Board(String),
+    Spell(String),
+    Square(String),
+    Effect(String),
+    Counter(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FenError::Board(fen) => write!(f, "Invalid position {}", fen),
+            FenError::Spell(token) => write!(f, "Invalid spell {}", token),
+            FenError::Square(token) => write!(f, "Invalid square {}", token),
+            FenError::Effect(token) => write!(f, "Invalid effect {}", token),
+            FenError::Counter(token) => write!(f, "Invalid counter {}", token),
+        }
+    }
+}
+
+fn parse_color(c: Option<char>, token: &str) -> Result<Color, FenError> {
+    match c {
+        Some('w') => Ok(Color::White),
+        Some('b') => Ok(Color::Black),
+        _ => Err(FenError::Effect(token.to_owned())),
+    }
+}
+
+/// a spell letter with an optional level, and whether the letter was upper case
+fn parse_spell(token: &str) -> Result<(Arc<dyn Spell>, bool), FenError> {
+    let spell = from_spell_token(token).ok_or_else(|| FenError::Spell(token.to_owned()))?;
+    Ok((spell, token.starts_with(|c: char| c.is_ascii_uppercase())))
+}
+
+fn parse_square_token(token: &str) -> Result<Square, FenError> {
+    parse_square(token).ok_or_else(|| FenError::Square(token.to_owned()))
+}
+
+/// A `spell@square` token, the square being optional
+struct Target {
+    spell: Arc<dyn Spell>,
+    visible: bool,
+    square: Option<Square>,
+}
+
+fn parse_target(token: &str) -> Result<Target, FenError> {
+    let (spell, square) = match token.split_once('@') {
+        Some((spell, square)) => (spell, Some(parse_square_token(square)?)),
+        None => (token, None),
+    };
+    let (spell, visible) = parse_spell(spell)?;
+    Ok(Target { spell, visible, square })
+}
+
+/// `-` for nothing, the items joined by `,` otherwise
+fn list(items: Vec<String>) -> String {
+    match items.is_empty() {
+        true => "-".to_owned(),
+        false => items.join(","),
+    }
+}
+
+fn items(field: &str) -> impl Iterator<Item = &str> {
+    field.split(',').filter(|item| !item.is_empty() && *item != "-")
+}
+
+impl Game {
+    /// the position as Magic FEN, see the `fen` module for the format
+    pub fn to_fen(&self) -> String {
+        let ply = self.moves.len();
+        let board = self.raw_board().to_string();
+        // chess doesn't track the move counters, the game keeps them itself
+        let position = board.split(' ').take(4).collect::<Vec<_>>().join(" ");
+        let fullmove = (self.first_ply + ply) / 2 + 1;
+
+        let spells = self.board.spells().iter().enumerate().filter_map(|(index, kind)| {
+            let square = self.board.spell_square(index);
+            match kind {
+                Kind::Transparent(spell) => Some(format!("{}@{}", spell_token(spell.as_ref()), square_name(square))),
+                Kind::Opaque(spell) => Some(format!("{}@{}", spell_token(spell.as_ref()).to_lowercase(), square_name(square))),
+                Kind::None => None,
+            }
+        }).collect();
+
+        let inventories = self.inventories.iter().map(|inventory| {
+            let held = inventory.spells().iter().map(|spell| spell_token(spell.as_ref())).collect::<String>();
+            if held.is_empty() { "-".to_owned() } else { held }
+        }).collect::<Vec<_>>().join("/");
+
+        let mut effects = Vec::new();
+        for color in chess::ALL_COLORS {
+            let mut traps = self.casted(color).values().collect::<Vec<_>>();
+            traps.sort_by_key(|cast| cast.square);
+            for cast in traps {
+                let square = cast.square.map(square_name).unwrap_or_default();
+                let expiry = cast.expires_at.map(|at| format!("~{}", at.saturating_sub(ply))).unwrap_or_default();
+                let shielded = cast.shielded.map(|side| format!("#{}", color_char(side))).unwrap_or_default();
+                effects.push(format!("{}{}@{}{}{}", color_char(color), spell_token(cast.spell.as_ref()), square, expiry, shielded));
+            }
+        }
+        for cast in &self.stack {
+            let square = cast.square.map(|square| format!("@{}", square_name(square))).unwrap_or_default();
+            effects.push(format!("!{}{}{}", color_char(cast.caster), spell_token(cast.spell.as_ref()), square));
+        }
+
+        let mut cooldowns = self.cooldowns.iter()
+            .filter(|(_, ready)| **ready > ply)
+            .filter_map(|((color, id), ready)| {
+                let spell = super::catalogue().into_iter().find(|spell| spell.identifier() == *id)?;
+                Some(format!("{}{}:{}", color_char(*color), symbol(spell.as_ref()), ready - ply))
+            })
+            .collect::<Vec<_>>();
+        cooldowns.sort();
+
+        let fen = format!(
+            "{} {} {} {} {} {} {} {}",
+            position, self.halfmoves, fullmove, list(spells), inventories, list(effects), self.casts_this_turn, list(cooldowns)
+        );
+        match self.hints {
+            [0, 0] => fen,
+            [white, black] => format!("{} {}/{}", fen, white, black),
+        }
+    }
+
+    /// a game set up from Magic FEN, see the `fen` module for the format
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields = fen.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 4 {
+            return Err(FenError::Board(fen.to_owned()));
+        }
+        let standard = fields.iter().take(6).copied().collect::<Vec<_>>().join(" ");
+        let board = Board::from_str(&standard).map_err(|_| FenError::Board(standard.clone()))?;
+        let magic = |index: usize| fields.get(6 + index).copied().unwrap_or("-");
+
+        let dims = (8, 8);
+        let mut spells: Vec<Kind<dyn Spell>> = (0..dims.0 * dims.1).map(|_| Kind::None).collect();
+        for token in items(magic(0)) {
+            let Target { spell, visible, square } = parse_target(token)?;
+            let square = square.ok_or_else(|| FenError::Square(token.to_owned()))?;
+            spells[square.get_file().to_index() * dims.1 + square.get_rank().to_index()] = match visible {
+                true => Kind::Transparent(spell),
+                false => Kind::Opaque(spell),
+            };
+        }
+
+        let mut inventories: [Inventory; 2] = Default::default();
+        for (inventory, held) in inventories.iter_mut().zip(magic(1).split('/')) {
+            // a letter starts the next spell, digits after it are its level
+            let mut tokens: Vec<String> = Vec::new();
+            for c in held.chars().filter(|c| *c != '-') {
+                match (c.is_ascii_digit(), tokens.last_mut()) {
+                    (true, Some(token)) => token.push(c),
+                    _ => tokens.push(c.to_string()),
+                }
+            }
+            let held = tokens.iter().map(|token| parse_spell(token).map(|(spell, _)| spell)).collect::<Result<_, _>>()?;
+            *inventory = Inventory::from_spells(held);
+        }
+
+        let halfmoves = match fields.get(4) {
+            Some(number) => number.parse().map_err(|_| FenError::Counter(number.to_string()))?,
+            None => 0,
+        };
+        let fullmove = match fields.get(5) {
+            Some(number) => number.parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(|| FenError::Counter(number.to_string()))?,
+            None => 1,
+        };
+        let mut game = Game {
+            first_ply: 2 * (fullmove - 1) + board.side_to_move().to_index(),
+            halfmoves,
+            board: UIBoard::new(board, dims, Some(Arc::new(spells))),
+            inventories,
+            ..Game::default()
+        };
+
+        for token in items(magic(2)) {
+            let mut chars = token.chars();
+            match chars.next() {
+                Some('!') => {
+                    let caster = parse_color(chars.next(), token)?;
+                    let Target { spell, square, .. } = parse_target(chars.as_str())?;
+                    game.stack.push(Cast::new(spell, square, caster));
+                },
+                c => {
+                    let owner = parse_color(c, token)?;
+                    let (trap, shielded) = match chars.as_str().split_once('#') {
+                        Some((trap, side)) => (trap, Some(parse_color(side.chars().next().filter(|_| side.len() == 1), token)?)),
+                        None => (chars.as_str(), None),
+                    };
+                    let (target, expiry) = match trap.split_once('~') {
+                        Some((target, expiry)) => (target, Some(expiry.parse().map_err(|_| FenError::Effect(token.to_owned()))?)),
+                        None => (trap, None),
+                    };
+                    let Target { spell, square, .. } = parse_target(target)?;
+                    let square = square.ok_or_else(|| FenError::Effect(token.to_owned()))?;
+                    let armed = spell.arm(square).ok_or_else(|| FenError::Effect(token.to_owned()))?;
+                    game.casted_mut(owner).insert((square.get_rank().to_index(), square.get_file().to_index()), Cast {
+                        expires_at: expiry,
+                        shielded,
+                        ..Cast::new(armed, Some(square), owner)
+                    });
+                },
+            }
+        }
+
+        game.casts_this_turn = match magic(3) {
+            "-" => 0,
+            casts => casts.parse().map_err(|_| FenError::Counter(casts.to_owned()))?,
+        };
+
+        let mut cooldowns = HashMap::new();
+        for token in items(magic(4)) {
+            let invalid = || FenError::Counter(token.to_owned());
+            let mut chars = token.chars();
+            let color = parse_color(chars.next(), token).map_err(|_| invalid())?;
+            let (spell, plies) = chars.as_str().split_once(':').ok_or_else(invalid)?;
+            let (spell, _) = parse_spell(spell)?;
+            cooldowns.insert((color, spell.identifier()), plies.parse().map_err(|_| invalid())?);
+        }
+        game.cooldowns = cooldowns;
+
+        if let Some(hints) = fields.get(11) {
+            let invalid = || FenError::Counter(hints.to_string());
+            let (white, black) = hints.split_once('/').ok_or_else(invalid)?;
+            game.hints = [white.parse().map_err(|_| invalid())?, black.parse().map_err(|_| invalid())?];
+        }
+        game.origin = game.to_fen();
+
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::square;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 w@a5,W@c5,R@d4,d@f4,S@h4 -/- - 0 -";
+
+    #[test]
+    fn writes_the_starting_position() {
+        assert_eq!(Game::new().to_fen(), START);
+    }
+
+    #[test]
+    fn reads_the_starting_position() {
+        assert_eq!(Game::from_fen(START).unwrap().board(), Game::new().board());
+    }
+
+    #[test]
+    fn plain_fen_has_no_spells() {
+        let game = Game::from_fen("8/8/8/4k3/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(game.board().spells().iter().all(|kind| matches!(kind, Kind::None)));
+        assert_eq!(game.acting_side(), Color::Black);
+    }
+
+    #[test]
+    fn magic_fields_round_trip() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 b - - 0 1 W2@c5,s@h4 WS/W3 bW@e5~3#w,!wS@c6 1 bW:2,wS:4";
+        let game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.inventory(Color::Black).spells()[0].level(), 3);
+        assert_eq!(game.trap_remaining(Color::Black, square("e5")), Some(2));
+        assert_eq!(game.casted(Color::Black).values().next().and_then(|cast| cast.shielded), Some(Color::White));
+        assert!(game.pending().is_some());
+        assert_eq!(game.to_fen(), fen);
+    }
+
+    #[test]
+    fn hints_taken_round_trip() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1 - -/- - 0 - 2/0";
+        let game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.hints_left(Color::White), 1);
+        assert_eq!(game.to_fen(), fen);
+    }
+
+    #[test]
+    fn move_counters_round_trip() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 7 23";
+        let game = Game::from_fen(fen).unwrap();
+        assert!(game.to_fen().starts_with(fen));
+        let game = game.make_move_new("e8d8".parse().unwrap());
+        assert!(game.to_fen().starts_with("3k4/8/8/8/8/8/4P3/4K3 w - - 8 24"));
+        let game = game.make_move_new("e2e4".parse().unwrap());
+        assert!(game.to_fen().starts_with("3k4/8/8/8/4P3/8/8/4K3 b - - 0 24"));
+    }
+
+    #[test]
+    fn rejects_unknown_spells() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1 X@c5 -/- - 0 -";
+        assert_eq!(Game::from_fen(fen).unwrap_err(), FenError::Spell("X".to_owned()));
+    }
+
+    #[test]
+    fn shielded_traps_round_trip() {
+        // black shields against white's warrior, which arms on e6 sparing black's pieces
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 - W/S - 0 -").unwrap();
+        let warrior = game.spells()[0].clone();
+        let game = game.cast_spell(warrior, square("e6")).unwrap();
+        let shield = game.inventory(Color::Black).spells()[0].clone();
+        let game = game.respond(shield).unwrap();
+        let fen = game.to_fen();
+        assert!(fen.contains(" wW@e6~6#b "), "{}", fen);
+        let back = Game::from_fen(&fen).unwrap();
+        assert_eq!(back.casted(Color::White).values().next().and_then(|cast| cast.shielded), Some(Color::Black));
+        assert_eq!(back.to_fen(), fen);
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 - -/- wW@e5#x 0 -").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+pub mod render;
+
+use std::sync::Arc;
+
+use chess::{Board, ChessMove, Color, Square};
+
+pub use render::*;
+use super::ai::{Action, SpellId};
+use super::board::{square_name, UIBoard};
+use super::notation::move_number;
+use super::{Game, Spell};
+
+/// Something that happened during the game
+#[derive(Clone, Debug)]
+pub enum Event {
+    Move { m: ChessMove, san: String },
+    /// a spell put on the stack, `hidden` if it arms a trap the opponent must not see
+    Cast { caster: Color, spell: Arc<dyn Spell>, square: Option<Square>, hidden: bool },
+    Respond { by: Color, spell: Arc<dyn Spell> },
+    /// a spell went off, either resolving from the stack or as a triggered trap
+    Effect { spell: Arc<dyn Spell>, square: Option<Square> },
+    Pickup { by: Color, spell: Arc<dyn Spell>, square: Square },
+}
+
+// spelled out because deriving can't compare the `Arc<dyn Spell>`s
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Event::Move { m: a, san: x }, Event::Move { m: b, san: y }) => a == b && x == y,
+            (Event::Cast { caster: a, spell: x, square: s, hidden: h }, Event::Cast { caster: b, spell: y, square: t, hidden: i }) => {
+                a == b && **x == **y && s == t && h == i
+            },
+            (Event::Respond { by: a, spell: x }, Event::Respond { by: b, spell: y }) => a == b && **x == **y,
+            (Event::Effect { spell: x, square: s }, Event::Effect { spell: y, square: t }) => **x == **y && s == t,
+            (Event::Pickup { by: a, spell: x, square: s }, Event::Pickup { by: b, spell: y, square: t }) => a == b && **x == **y && s == t,
+            _ => false,
+        }
+    }
+}
+
+/// An event together with the position right after it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// counted from white's first move, also in games set up from a later position
+    pub ply: usize,
+    pub event: Event,
+    pub board: UIBoard,
+}
+
+impl Entry {
+    /// how the entry reads in the move list for `viewer`, e.g. "12... ✦Jihadi Warrior@e4"
+    pub fn label(&self, viewer: Color) -> String {
+        let at = |square: &Option<Square>| square.map(square_name).unwrap_or_default();
+        let text = match &self.event {
+            Event::Move { san, .. } => san.clone(),
+            Event::Cast { caster, spell, hidden: true, .. } if *caster != viewer => format!("✦{}@?", spell.name()),
+            Event::Cast { spell, square, .. } => format!("✦{}@{}", spell.name(), at(square)),
+            Event::Respond { spell, .. } => format!("↺{}", spell.name()),
+            Event::Effect { spell, square } => format!("💥{}@{}", spell.name(), at(square)),
+            Event::Pickup { spell, square, .. } => format!("+{}@{}", spell.name(), square_name(*square)),
+        };
+        format!("{} {}", move_number(self.ply), text)
+    }
+
+    /// a full sentence describing the entry, used for tooltips
+    pub fn describe(&self, viewer: Color) -> String {
+        let at = |square: &Option<Square>| square.map(|s| format!(" on {}", square_name(s))).unwrap_or_default();
+        match &self.event {
+            Event::Move { m, san } => format!("{:?} plays {} ({})", self.mover(), san, m),
+            Event::Cast { caster, spell, hidden: true, .. } if *caster != viewer => format!("{:?} sets a hidden {}", caster, spell.name()),
+            Event::Cast { caster, spell, square, .. } => format!("{:?} casts {}{}", caster, spell.name(), at(square)),
+            Event::Respond { by, spell } => format!("{:?} responds with {}", by, spell.name()),
+            Event::Effect { spell, square } => format!("{} takes effect{}", spell.name(), at(square)),
+            Event::Pickup { by, spell, square } => format!("{:?} picks up {} on {}", by, spell.name(), square_name(*square)),
+        }
+    }
+
+    /// the entry as read out to screen readers, with moves spelled out rather than in SAN
+    pub fn announce(&self, viewer: Color) -> String {
+        let (m, san) = match &self.event {
+            Event::Move { m, san } => (m, san),
+            _ => return self.describe(viewer),
+        };
+        let check = match san.chars().last() {
+            Some('#') => ", checkmate",
+            Some('+') => ", check",
+            _ => "",
+        };
+        let side = format!("{:?}", self.mover());
+        if san.starts_with("O-O-O") {
+            return format!("{} castles queenside{}", side, check);
+        }
+        if san.starts_with("O-O") {
+            return format!("{} castles kingside{}", side, check);
+        }
+        let piece = self.board.board().piece_on(m.get_dest()).map(|p| format!("{:?}", p).to_lowercase()).unwrap_or_default();
+        let action = if san.contains('x') { "takes on" } else { "to" };
+        format!("{} {} {} {} {}{}", side, piece, square_name(m.get_source()), action, square_name(m.get_dest()), check)
+    }
+
+    /// the side whose turn it was
+    fn mover(&self) -> Color {
+        match self.ply % 2 {
+            0 => Color::White,
+            _ => Color::Black,
+        }
+    }
+}
+
+/// A decision as a record keeps it, moves in a notation that takes the position to read
+#[derive(Clone, Debug, PartialEq)]
+pub enum Recorded<M> {
+    Move(M),
+    Cast(SpellId, Square),
+    Respond(SpellId),
+}
+
+/// One step of a replay
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replayed {
+    pub action: Action,
+    pub mover: Color,
+    /// index of the record played, `None` for a cast nobody answered resolving on its own
+    pub record: Option<usize>,
+    /// the game right after the step
+    pub game: Game,
+}
+
+impl Game {
+    /// plays `records` one after another from this position, reading moves with `read_move`. A cast
+    /// nobody answered resolves before the next move or cast. `Err` holds the index of the first
+    /// record that can't be played
+    pub fn replay<M>(&self, records: impl IntoIterator<Item = Recorded<M>>, read_move: impl Fn(&Board, &M) -> Option<ChessMove>) -> Result<Vec<Replayed>, usize> {
+        let mut steps = Vec::new();
+        let mut current = self.clone();
+        for (i, record) in records.into_iter().enumerate() {
+            if current.pending().is_some() && !matches!(record, Recorded::Respond(_)) {
+                let mover = current.acting_side();
+                current = current.resolve();
+                steps.push(Replayed { action: Action::Pass, mover, record: None, game: current.clone() });
+            }
+            let action = match record {
+                Recorded::Move(m) => Action::Move(read_move(current.raw_board(), &m).ok_or(i)?),
+                Recorded::Cast(spell, square) => Action::Cast(spell, square),
+                Recorded::Respond(spell) => Action::Respond(spell),
+            };
+            let mover = current.acting_side();
+            current = current.apply(&action).ok_or(i)?;
+            steps.push(Replayed { action, mover, record: Some(i), game: current.clone() });
+        }
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{opening, square};
+
+    fn as_played(m: &ChessMove) -> Option<ChessMove> {
+        Some(*m)
+    }
+
+    #[test]
+    fn unanswered_casts_resolve_before_the_next_move() {
+        let game = opening();
+        let warrior = SpellId::of(game.spells()[0].as_ref());
+        let records = [Recorded::Cast(warrior, square("c6")), Recorded::Move(ChessMove::new(square("g1"), square("f3"), None))];
+        let steps = game.replay(records, |_, m| as_played(m)).unwrap();
+        let actions = steps.iter().map(|step| (step.action, step.record)).collect::<Vec<_>>();
+        assert_eq!(actions, vec![
+            (Action::Cast(warrior, square("c6")), Some(0)),
+            (Action::Pass, None),
+            (Action::Move(ChessMove::new(square("g1"), square("f3"), None)), Some(1)),
+        ]);
+        assert_eq!(steps[1].mover, Color::Black);
+        assert!(steps[2].game.pending().is_none());
+    }
+
+    #[test]
+    fn responses_answer_the_pending_cast() {
+        let game = opening();
+        let warrior = SpellId::of(game.spells()[0].as_ref());
+        let steps = game.replay([Recorded::<ChessMove>::Cast(warrior, square("c6")), Recorded::Respond(warrior)], |_, m| as_played(m));
+        // black holds no warrior anymore, it was cast on e5
+        assert_eq!(steps, Err(1));
+    }
+
+    #[test]
+    fn unreadable_moves_stop_the_replay() {
+        let steps = Game::new().replay([Recorded::Move("e2e5")], |board, m| {
+            m.parse::<ChessMove>().ok().filter(|m| board.legal(*m))
+        });
+        assert_eq!(steps, Err(0));
+    }
+}
+
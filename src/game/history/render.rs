@@ -0,0 +1,101 @@
+use chess::Color;
+use gloo::dialogs::{alert, prompt};
+use yew::prelude::*;
+
+use crate::game::analysis::Analysis;
+use crate::game::{share, Game};
+use crate::window;
+
+/// copies the game as PGN to the clipboard
+fn export(game: &UseStateHandle<Game>) -> Callback<MouseEvent> {
+    let game = game.clone();
+    Callback::from(move |_| {
+        let _ = window().navigator().clipboard().write_text(&game.to_pgn());
+    })
+}
+
+/// copies a link opening the game, or only its current position, to the clipboard
+fn share(game: &UseStateHandle<Game>, whole: bool) -> Callback<MouseEvent> {
+    let game = game.clone();
+    Callback::from(move |_| {
+        let code = match whole {
+            true => game.share_game(),
+            false => Ok(game.share_position()),
+        };
+        match code {
+            Ok(code) => drop(window().navigator().clipboard().write_text(&share::link(&code))),
+            Err(e) => alert(&e.to_string()),
+        }
+    })
+}
+
+/// replaces the game with one pasted as PGN
+fn import(game: &UseStateHandle<Game>, viewing: &UseStateHandle<Option<usize>>) -> Callback<MouseEvent> {
+    let game = game.clone();
+    let viewing = viewing.clone();
+    Callback::from(move |_| {
+        let pgn = match prompt("Paste a game in PGN", None) {
+            Some(pgn) => pgn,
+            None => return,
+        };
+        match Game::from_pgn(&pgn) {
+            Ok(imported) => {
+                viewing.set(None);
+                game.set(imported);
+            },
+            Err(e) => alert(&e.to_string()),
+        }
+    })
+}
+
+/// opens the analysis of the game at its final position
+fn analyse(game: &UseStateHandle<Game>, analysis: &UseStateHandle<Option<Analysis>>, step: &UseStateHandle<usize>) -> Callback<MouseEvent> {
+    let game = game.clone();
+    let analysis = analysis.clone();
+    let step = step.clone();
+    Callback::from(move |_| {
+        match Analysis::new(&game) {
+            Ok(started) => {
+                step.set(started.steps().len());
+                analysis.set(Some(started));
+            },
+            Err(e) => alert(&e.to_string()),
+        }
+    })
+}
+
+/// the move list, clicking an entry shows the position right after it
+pub fn render(game: &UseStateHandle<Game>, viewing: &UseStateHandle<Option<usize>>, analysis: &UseStateHandle<Option<Analysis>>, step: &UseStateHandle<usize>, viewer: Color) -> Html {
+    let back = {
+        let viewing = viewing.clone();
+        Callback::from(move |_| viewing.set(None))
+    };
+    html! {
+        <div class="history--wrapper p-6 shadow-2xl rounded-xl">
+            <div class="history--actions">
+                <button class="menu--pass rounded-lg" onclick={export(game)}>{"Copy PGN"}</button>
+                <button class="menu--pass rounded-lg" onclick={import(game, viewing)}>{"Load PGN"}</button>
+                <button class="menu--pass rounded-lg" onclick={share(game, true)}>{"Copy link to game"}</button>
+                <button class="menu--pass rounded-lg" onclick={share(game, false)}>{"Copy link to position"}</button>
+            </div>
+            if game.is_over() {
+                <button class="menu--pass rounded-lg" onclick={analyse(game, analysis, step)}>{"Analyse game"}</button>
+            }
+            if viewing.is_some() {
+                <button class="menu--pass rounded-lg" onclick={back}>{"Back to game"}</button>
+            }
+            <ol class="history--list">
+                {game.history().iter().enumerate().map(|(i, entry)| {
+                    let onclick = {
+                        let viewing = viewing.clone();
+                        Callback::from(move |_| viewing.set(Some(i)))
+                    };
+                    let classes = classes!("history--entry", (**viewing == Some(i)).then_some("history--entry--active"));
+                    html! {
+                        <li class={classes} title={entry.describe(viewer)} {onclick}>{entry.label(viewer)}</li>
+                    }
+                }).collect::<Html>()}
+            </ol>
+        </div>
+    }
+}
@@ -2,46 +2,76 @@ use std::sync::Arc;
 
 use super::{Spell, JihadiWarrior};
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Inventory {
     spells: Vec<Arc<dyn Spell>>,
 }
 
-impl Default for Inventory {
-    fn default() -> Self {
-        Self { spells: Vec::new() }
-    }
-}
-
 impl Inventory {
+    #[allow(dead_code)]
     pub fn test() -> Self {
         Self {
             spells: vec![Arc::new(JihadiWarrior::default())],
         }
     }
+    pub fn from_spells(spells: Vec<Arc<dyn Spell>>) -> Self {
+        Self {
+            spells
+        }
+    }
+    /// adds a spell, merging it into a held copy of the same spell if that one levels up
     pub fn collect_spell(self, spell: Arc<dyn Spell>) -> Self {
         let mut spells = self.spells;
-        spells.push(spell);
+        let merged = spells.iter().enumerate()
+            .filter(|(_, s)| s.identifier() == spell.identifier())
+            .find_map(|(i, s)| s.merge(spell.as_ref()).map(|m| (i, m)));
+        match merged {
+            Some((i, m)) => spells[i] = m,
+            None => spells.push(spell),
+        }
         Self {
             spells
         }
     }
-    pub fn remove_spell(self, id: u32) -> Self {
+    /// removes the held copy `spell` points to, or failing that one equal to it, if any is held
+    pub fn remove_spell(self, spell: &Arc<dyn Spell>) -> Self {
+        let index = self.position(spell);
         let mut spells = self.spells;
-        let spells = spells.iter().filter_map(|s| {
-            match s.identifier() {
-                id => {
-                    Some(s.clone())
-                }
-                _ => None
-            }
-        }).collect::<Vec<_>>();
+        if let Some(index) = index {
+            spells.remove(index);
+        }
         Self {
             spells
         }
     }
 
+    /// where `spell` is held: that very copy, or else one equal to it
+    fn position(&self, spell: &Arc<dyn Spell>) -> Option<usize> {
+        self.spells.iter().position(|s| Arc::ptr_eq(s, spell))
+            .or_else(|| self.spells.iter().position(|s| **s == **spell))
+    }
+
+    pub fn holds(&self, spell: &Arc<dyn Spell>) -> bool {
+        self.position(spell).is_some()
+    }
+
     pub fn spells(&self) -> Vec<Arc<dyn Spell>> {
         self.spells.to_vec()
     } 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::square;
+    use crate::game::{restore, Game};
+
+    #[test]
+    fn casting_spends_the_copy_cast() {
+        let (strong, weak) = (restore(0, 3).unwrap(), restore(0, 1).unwrap());
+        let game = Game::new().with_inventories([Inventory::from_spells(vec![strong, weak.clone()]), Inventory::default()]);
+        let game = game.cast_spell(weak, square("e5")).unwrap();
+        let levels = game.inventory(chess::Color::White).spells().iter().map(|spell| spell.level()).collect::<Vec<_>>();
+        assert_eq!(levels, vec![3]);
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use chess::Square;
+use log::info;
+use wasm_bindgen::JsCast;
+use yew::prelude::*;
+use super::board::{square_name, View};
+use super::moves::select;
+use super::{Game, Spell};
+use crate::document;
+
+/// Keyboard play: the square the board's focus is on and a spell picked from the menu waiting for a target
+#[derive(Clone, Debug, Default)]
+pub struct Focus {
+    pub cursor: Option<Square>,
+    pub aiming: Option<Arc<dyn Spell>>,
+}
+
+impl Focus {
+    /// the focused square, the bottom left corner until the board has had focus
+    fn at(&self, view: &View) -> Square {
+        self.cursor.unwrap_or_else(|| view.corner())
+    }
+
+    /// `spell` picked as the next target's spell, focus stays where it was
+    fn aiming(&self, view: &View, spell: Arc<dyn Spell>) -> Focus {
+        Focus { cursor: Some(self.at(view)), aiming: Some(spell) }
+    }
+
+    /// no spell aimed anymore, focus stays where it was
+    fn cancelled(&self, view: &View) -> Focus {
+        Focus { cursor: Some(self.at(view)), aiming: None }
+    }
+}
+
+/// What a key pressed on the board asks for
+#[derive(Debug, PartialEq)]
+enum Press {
+    /// focus the square, the same one when walking off the edge
+    Walk(Square),
+    Act(Square),
+    Cancel,
+}
+
+fn press(key: &str, focus: &Focus, view: &View) -> Option<Press> {
+    let cursor = focus.at(view);
+    let (cols, rows) = match key {
+        "ArrowUp" => (0, -1),
+        "ArrowDown" => (0, 1),
+        "ArrowLeft" => (-1, 0),
+        "ArrowRight" => (1, 0),
+        "Enter" | " " => return Some(Press::Act(cursor)),
+        "Escape" => return Some(Press::Cancel),
+        _ => return None,
+    };
+    Some(Press::Walk(view.step(cursor, cols, rows).unwrap_or(cursor)))
+}
+
+/// the game after casting `spell` at `square`, unchanged if it can't be cast there; the aim is spent either way
+fn fire(game: &Game, spell: Arc<dyn Spell>, square: Square) -> (Game, Focus) {
+    let game = match game.cast_spell(spell, square) {
+        Ok(game) => game,
+        Err(e) => {
+            info!("{}", e);
+            game.clone()
+        },
+    };
+    (game, Focus { cursor: Some(square), aiming: None })
+}
+
+/// moves DOM focus to the cell of `square`
+fn focus_cell(square: Square) {
+    let cell = document().get_element_by_id(&square_name(square)).and_then(|el| el.dyn_into::<web_sys::HtmlElement>().ok());
+    if let Some(cell) = cell {
+        let _ = cell.focus();
+    }
+}
+
+/// casts the aimed spell at `square`, or selects / moves like a click when nothing is aimed
+pub fn activate(state: &UseStateHandle<Game>, selected: &UseStateHandle<Option<Square>>, focus: &UseStateHandle<Focus>, square: Square) {
+    match focus.aiming.clone() {
+        Some(spell) => {
+            let (game, aimed) = fire(state, spell, square);
+            state.set(game);
+            focus.set(aimed);
+        },
+        None => {
+            let (game, selection) = select(state, **selected, square);
+            selected.set(selection);
+            state.set(game);
+            focus.set(Focus { cursor: Some(square), ..(**focus).clone() });
+        },
+    }
+}
+
+/// arrow keys walk the board as it is shown, enter or space act on the focused square, escape lets go
+pub fn navigate(state: &UseStateHandle<Game>, selected: &UseStateHandle<Option<Square>>, focus: &UseStateHandle<Focus>) -> Callback<KeyboardEvent> {
+    let state = state.clone();
+    let selected = selected.clone();
+    let focus = focus.clone();
+    Callback::from(move |e: KeyboardEvent| {
+        let view = state.view();
+        match press(&e.key(), &focus, &view) {
+            Some(Press::Walk(next)) => {
+                e.prevent_default();
+                focus.set(Focus { cursor: Some(next), ..(*focus).clone() });
+                focus_cell(next);
+            },
+            Some(Press::Act(square)) => {
+                e.prevent_default();
+                activate(&state, &selected, &focus, square);
+            },
+            Some(Press::Cancel) => {
+                selected.set(None);
+                focus.set(focus.cancelled(&view));
+            },
+            None => {},
+        }
+    })
+}
+
+/// picks `spell` from the menu so the next activated square is its target
+pub fn aim(game: &Game, focus: &UseStateHandle<Focus>, spell: Arc<dyn Spell>) {
+    let aimed = focus.aiming(&game.view(), spell);
+    if let Some(cursor) = aimed.cursor {
+        focus_cell(cursor);
+    }
+    focus.set(aimed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::square;
+    use crate::game::Inventory;
+
+    #[test]
+    fn arrows_walk_the_board_as_shown() {
+        let white = View::new((8, 8), chess::Color::White);
+        let black = View::new((8, 8), chess::Color::Black);
+        let start = Focus::default();
+        assert_eq!(press("ArrowUp", &start, &white), Some(Press::Walk(square("a2"))));
+        assert_eq!(press("ArrowUp", &start, &black), Some(Press::Walk(square("h7"))));
+        assert_eq!(press("ArrowRight", &start, &black), Some(Press::Walk(square("g8"))));
+        assert_eq!(press("Tab", &start, &white), None);
+    }
+
+    #[test]
+    fn arrows_stop_at_the_edge() {
+        let view = View::new((8, 8), chess::Color::White);
+        let corner = Focus::default();
+        assert_eq!(press("ArrowLeft", &corner, &view), Some(Press::Walk(square("a1"))));
+        assert_eq!(press("ArrowDown", &corner, &view), Some(Press::Walk(square("a1"))));
+        let top = Focus { cursor: Some(square("h8")), aiming: None };
+        assert_eq!(press("ArrowRight", &top, &view), Some(Press::Walk(square("h8"))));
+        assert_eq!(press("ArrowUp", &top, &view), Some(Press::Walk(square("h8"))));
+    }
+
+    #[test]
+    fn aiming_keeps_focus_until_cancelled() {
+        let game = Game::new().with_inventories([Inventory::test(), Inventory::test()]);
+        let view = game.view();
+        let warrior = game.spells()[0].clone();
+        let aimed = Focus::default().aiming(&view, warrior.clone());
+        assert_eq!(aimed.cursor, Some(square("a1")));
+        assert!(aimed.aiming.is_some());
+        let moved = Focus { cursor: Some(square("e5")), ..aimed };
+        assert_eq!(press("Enter", &moved, &view), Some(Press::Act(square("e5"))));
+        assert_eq!(press("Escape", &moved, &view), Some(Press::Cancel));
+        let cancelled = moved.cancelled(&view);
+        assert_eq!(cancelled.cursor, Some(square("e5")));
+        assert!(cancelled.aiming.is_none());
+    }
+
+    #[test]
+    fn firing_spends_the_aim() {
+        let game = Game::new().with_inventories([Inventory::test(), Inventory::test()]);
+        let warrior = game.spells()[0].clone();
+        let (cast, focus) = fire(&game, warrior, square("e5"));
+        assert!(cast.pending().is_some());
+        assert_eq!((focus.cursor, focus.aiming.is_none()), (Some(square("e5")), true));
+        // nothing in hand, the game stays as it was
+        let (same, focus) = fire(&Game::new(), game.spells()[0].clone(), square("e5"));
+        assert!(same.pending().is_none());
+        assert!(focus.aiming.is_none());
+    }
+}
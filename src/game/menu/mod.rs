@@ -1,19 +1,172 @@
-use lazy_static::__Deref;
-use log::info;
 use yew::prelude::*;
-use super::Game;
+use super::board::square_name;
+use super::{Game, Speed};
+use super::spell::{self, Detail};
+use super::touch::{self, Drag, Payload};
+use super::keys::{self, Focus};
+use super::ai::{Computer, HintState};
+use super::draft::Draft;
 
-pub fn render(game: &UseStateHandle<Game>) -> Html {
+fn render_pending(game: &UseStateHandle<Game>) -> Html {
+    let cast = match game.pending() {
+        Some(cast) => cast,
+        None => return html! {},
+    };
+    // a trap's square stays hidden from the side responding to it
+    let target = match cast.square {
+        Some(s) if game.trap_for(cast.spell.as_ref(), s).is_none() => format!(" on {}", square_name(s)),
+        _ => String::new(),
+    };
+    let state = game.clone();
+    html! {
+        <div class="menu--pending p-4 rounded-lg" role="status">
+            <span class="text-sm">{format!("{:?} casts {}{} — {:?} may respond", cast.caster, cast.spell.name(), target, !cast.caster)}</span>
+            <button class="menu--pass rounded-lg" onclick={Callback::from(move |_| state.set(state.resolve()))}>{"Pass"}</button>
+        </div>
+    }
+}
+
+fn render_orientation(game: &UseStateHandle<Game>) -> Html {
+    let flip = {
+        let state = game.clone();
+        Callback::from(move |_| state.set(state.flip_board()))
+    };
+    let rotate = {
+        let state = game.clone();
+        Callback::from(move |_| state.set(state.set_auto_rotate(!state.auto_rotate())))
+    };
+    html! {
+        <div class="menu--orientation">
+            <button class="menu--pass rounded-lg" onclick={flip}>{"Flip board"}</button>
+            <label class="text-sm">
+                <input type="checkbox" checked={game.auto_rotate()} onclick={rotate} />
+                {" Rotate to side to move"}
+            </label>
+        </div>
+    }
+}
+
+/// the switch for playing against the computer, which takes the side at the top of the board,
+/// and the way back to the new game screen to set it up properly
+fn render_opponent(game: &UseStateHandle<Game>, computer: &UseStateHandle<Option<Computer>>, draft: &UseStateHandle<Option<Draft>>) -> Html {
+    let restart = {
+        let draft = draft.clone();
+        Callback::from(move |_| draft.set(Some(Draft::new(js_sys::Date::now() as u64))))
+    };
+    let toggle = {
+        let game = game.clone();
+        let computer = computer.clone();
+        Callback::from(move |_| computer.set(match *computer {
+            Some(_) => None,
+            None => Some(Computer::new(!game.orientation())),
+        }))
+    };
+    let label = match **computer {
+        Some(computer) => format!(" Computer plays {:?} ({}, {})", computer.side, computer.level.name(), computer.personality.name()),
+        None => " Play against the computer".to_owned(),
+    };
+    html! {
+        <div class="menu--opponent">
+            <label class="text-sm">
+                <input type="checkbox" checked={computer.is_some()} onclick={toggle} />
+                {label}
+            </label>
+            <button class="menu--pass rounded-lg" onclick={restart}>{"New game"}</button>
+        </div>
+    }
+}
+
+/// the hint button and, while it still fits the game, the last hint's explanation. The app
+/// searches for a hint once it's asked for
+fn render_hint(game: &UseStateHandle<Game>, hint: &UseStateHandle<Option<HintState>>) -> Html {
+    let left = game.hints_left(game.acting_side());
+    let searching = matches!(**hint, Some(HintState::Searching));
+    let ask = {
+        let hint = hint.clone();
+        Callback::from(move |_| hint.set(Some(HintState::Searching)))
+    };
+    let label = match searching {
+        true => "Thinking…".to_owned(),
+        false => format!("Hint ({} left)", left),
+    };
+    let found = match &**hint {
+        Some(HintState::Found(found)) => Some(found),
+        _ => None,
+    };
+    html! {
+        <div class="menu--hint">
+            <button class="menu--pass rounded-lg" onclick={ask} disabled={left == 0 || searching}>{label}</button>
+            if let Some(hint) = found.filter(|hint| hint.fits(game)) {
+                <span class="text-sm" role="status">{hint.explanation.clone()}</span>
+            }
+        </div>
+    }
+}
+
+pub fn render(game: &UseStateHandle<Game>, drag: &UseStateHandle<Option<Drag>>, detail: &UseStateHandle<Option<Detail>>, focus: &UseStateHandle<Focus>, computer: &UseStateHandle<Option<Computer>>, draft: &UseStateHandle<Option<Draft>>, hint: &UseStateHandle<Option<HintState>>) -> Html {
     //let state = game.clone();
+    if computer.is_some_and(|computer| computer.side == game.acting_side()) {
+        return html! {
+            <div class="menu--wrapper p-6 shadow-2xl rounded-xl">
+                {render_orientation(game)}
+                {render_opponent(game, computer, draft)}
+                <span class="menu--casts text-sm" role="status">{format!("{:?} is thinking…", game.acting_side())}</span>
+            </div>
+        };
+    }
     html! {
         <div class="menu--wrapper p-6 shadow-2xl rounded-xl">
+            {render_orientation(game)}
+            {render_opponent(game, computer, draft)}
+            <span class="menu--casts text-sm">{format!("{:?} — casts left: {}", game.acting_side(), game.casts_left())}</span>
+            {render_hint(game, hint)}
+            {render_pending(game)}
             {game.spells().iter().map(|spell| {
                 let state = game.clone();
                 let spell = spell.clone();
                 let classes = spell.class_list();
                 let name = spell.name();
+                let level = spell.level();
+                let castable = game.can_cast(spell.as_ref());
+                let (wrapper, title) = match castable {
+                    Ok(()) => ("spell--wrapper p-6 shadow-xl rounded-lg", String::new()),
+                    Err(e) => ("spell--wrapper spell--wrapper--disabled p-6 shadow-xl rounded-lg", e.to_string()),
+                };
+                let instant = spell.speed() == Speed::Instant;
+                // instants answer the pending cast right away, anything else waits for a target square
+                let activate = {
+                    let state = state.clone();
+                    let spell = spell.clone();
+                    let focus = focus.clone();
+                    let castable = castable.is_ok();
+                    move || match instant {
+                        true if state.pending().is_some() => state.set(super::moves::respond(&state, spell.clone())),
+                        false if castable => keys::aim(&state, &focus, spell.clone()),
+                        _ => {},
+                    }
+                };
+                let onclick = {
+                    let activate = activate.clone();
+                    Callback::from(move |_| activate())
+                };
+                let onkeydown = Callback::from(move |e: KeyboardEvent| {
+                    if e.key() == "Enter" || e.key() == " " {
+                        e.prevent_default();
+                        activate()
+                    }
+                });
+                let aiming = focus.aiming.as_ref().is_some_and(|aimed| aimed.identifier() == spell.identifier());
+                let wrapper = classes!(wrapper, aiming.then_some("spell--wrapper--aiming"));
+                let label = format!("{}, level {}{}", name, level, if title.is_empty() { String::new() } else { format!(", {}", title) });
+                let draggable = castable.is_ok() && !instant;
+                let onpointerdown = match draggable {
+                    true => touch::start(drag, Payload::Spell(spell.clone())),
+                    false => Callback::noop(),
+                };
+                let (onmouseenter, onmouseleave) = spell::hover_detail(detail, spell.clone(), None);
+                let oncontextmenu = spell::press_detail(detail, spell.clone(), None);
                 html! {
-                    <div id={format!("spell-{:?}", spell.identifier())} draggable="true" class="spell--wrapper p-6 shadow-xl rounded-lg" ondragend={
+                    <div id={format!("spell-{:?}", spell.identifier())} draggable={draggable.to_string()} class={wrapper} {title} role="button" tabindex="0" aria-label={label} aria-disabled={castable.is_err().to_string()} aria-pressed={aiming.to_string()} {onclick} {onkeydown} {onpointerdown} {onmouseenter} {onmouseleave} {oncontextmenu} ondragend={
                         Callback::from(move |e| {
                             let game = state.clone();
                             let game_ = super::moves::cast_spell(&game, spell.clone(), e);
@@ -25,9 +178,10 @@ pub fn render(game: &UseStateHandle<Game>) -> Html {
                         </div>
                         </div>
                         <span class="text-base">{name}</span>
+                        <span class="spell--level rounded-lg">{format!("Lv {}", level)}</span>
                     </div>
                 }
             }).collect::<Vec<_>>()}
         </div>
     }
-}
\ No newline at end of file
+}
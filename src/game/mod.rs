@@ -3,21 +3,63 @@ pub mod moves;
 pub mod spell;
 pub mod menu;
 pub mod inventory;
+pub mod rules;
+pub mod stack;
+pub mod draft;
+pub mod touch;
+pub mod notation;
+pub mod fen;
+pub mod pgn;
+pub mod history;
+pub mod theme;
+pub mod keys;
+pub mod save;
+pub mod share;
+pub mod ai;
+pub mod uci;
+pub mod analysis;
+#[cfg(test)]
+mod testing;
 
 use std::{collections::HashMap, sync::Arc};
 
-pub use moves::make_move;
+use log::info;
+
 pub use spell::*;
-pub use menu::*;
 pub use inventory::*;
+pub use rules::*;
+pub use stack::*;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Game {
     board: board::UIBoard,
     moves: Vec<chess::ChessMove>,
-    inventory: Inventory,
-    casted_mine: HashMap<(usize, usize), Arc<dyn Spell>>,
-    casted_other: HashMap<(usize, usize), Arc<dyn Spell>>,
+    inventories: [Inventory; 2],
+    /// the color played from this side of the screen
+    player: chess::Color,
+    /// show the board upside down relative to `player`
+    flipped: bool,
+    /// keep the side that may act at the bottom, for pass and play
+    auto_rotate: bool,
+    /// traps armed by `player`, keyed by (rank, file)
+    casted_mine: HashMap<(usize, usize), Cast>,
+    /// traps armed by the opponent, keyed by (rank, file)
+    casted_other: HashMap<(usize, usize), Cast>,
+    rules: CastingRules,
+    casts_this_turn: u8,
+    cooldowns: HashMap<(chess::Color, u32), usize>,
+    stack: Vec<Cast>,
+    shielded: Option<chess::Color>,
+    /// everything that happened so far, in order
+    history: Vec<history::Entry>,
+    /// Magic FEN of the position play started from, empty for the standard start without spells in hand
+    origin: String,
+    /// hints taken so far, indexed by color
+    hints: [u8; 2],
+    /// plies played before the position play started from, as its move number and side tell
+    first_ply: usize,
+    /// plies since the last capture or pawn move, as FEN counts them
+    halfmoves: usize,
 }
 
 impl Default for Game {
@@ -25,17 +67,50 @@ impl Default for Game {
         Self {
             board: board::UIBoard::default(),
             moves: Vec::new(),
-            inventory: Inventory::default(),
+            inventories: Default::default(),
+            player: chess::Color::White,
+            flipped: false,
+            auto_rotate: false,
             casted_mine: HashMap::new(),
             casted_other: HashMap::new(),
+            rules: CastingRules::default(),
+            casts_this_turn: 0,
+            cooldowns: HashMap::new(),
+            stack: Vec::new(),
+            shielded: None,
+            history: Vec::new(),
+            origin: String::new(),
+            hints: [0; 2],
+            first_ply: 0,
+            halfmoves: 0,
         }
     }
 }
 
 impl Game {
     pub fn new() -> Self {
-        Self::default()
+        Self::with_rules(CastingRules::default())
+    }
+
+    pub fn with_rules(rules: CastingRules) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
     }
+
+    /// the same game with both sides starting from the given inventories, indexed by color
+    pub fn with_inventories(self, inventories: [Inventory; 2]) -> Self {
+        let game = Self {
+            inventories,
+            ..self
+        };
+        Self {
+            origin: game.to_fen(),
+            ..game
+        }
+    }
+
     pub fn board(&self) -> &board::UIBoard {
         &self.board
     }
@@ -44,9 +119,97 @@ impl Game {
         self.board.board()
     }
 
+    /// the color shown at the bottom of the board
+    pub fn orientation(&self) -> chess::Color {
+        match (self.auto_rotate, self.flipped) {
+            (true, _) => self.acting_side(),
+            (false, false) => self.player,
+            (false, true) => !self.player,
+        }
+    }
+
+    pub fn view(&self) -> board::View {
+        board::View::new(self.board.dims(), self.orientation())
+    }
+
+    pub fn flip_board(&self) -> Self {
+        Self {
+            flipped: !self.flipped,
+            auto_rotate: false,
+            ..self.clone()
+        }
+    }
+
+    pub fn auto_rotate(&self) -> bool {
+        self.auto_rotate
+    }
+
+    pub fn set_auto_rotate(&self, auto_rotate: bool) -> Self {
+        Self {
+            auto_rotate,
+            ..self.clone()
+        }
+    }
+
+    pub fn casts_left(&self) -> u8 {
+        self.rules.max_casts_per_turn.saturating_sub(self.casts_this_turn)
+    }
+
     pub fn make_move(&mut self, m: chess::ChessMove) {
+        let mover = self.raw_board().side_to_move();
+        let san = notation::san(self.raw_board(), m);
+        let quiet = self.raw_board().piece_on(m.get_source()) != Some(chess::Piece::Pawn) && self.raw_board().piece_on(m.get_dest()).is_none();
+        self.halfmoves = if quiet { self.halfmoves + 1 } else { 0 };
         self.board = self.board.make_move_new(m);
+        self.record(self.moves.len(), history::Event::Move { m, san });
         self.moves.push(m);
+        self.casts_this_turn = 0;
+        self.trigger_traps(m, mover);
+    }
+
+    /// detonates the opponent's traps on or along the path of a move
+    fn trigger_traps(&mut self, m: chess::ChessMove, mover: chess::Color) {
+        let path = chess::between(m.get_source(), m.get_dest()) | chess::BitBoard::from_square(m.get_dest());
+        let triggered = path.filter_map(|square| {
+            self.casted_mut(!mover).remove(&(square.get_rank().to_index(), square.get_file().to_index()))
+        }).collect::<Vec<_>>();
+        for cast in triggered {
+            let square = cast.square.map(board::square_name).unwrap_or_default();
+            info!("{:?} triggered {} on {}", mover, cast.spell.name(), square);
+            *self = self.clone().detonate(cast, self.moves.len() - 1);
+        }
+        let ply = self.moves.len();
+        self.casted_mut(!mover).retain(|_, cast| cast.expires_at.is_none_or(|expiry| expiry > ply));
+    }
+
+    /// how many of its owner's turns the trap on `square` stays armed
+    pub fn trap_remaining(&self, color: chess::Color, square: chess::Square) -> Option<usize> {
+        let cast = self.casted(color).get(&(square.get_rank().to_index(), square.get_file().to_index()))?;
+        cast.expires_at.map(|expiry| expiry.saturating_sub(self.moves.len()).div_ceil(2))
+    }
+
+    /// the armed spell `color` hides on `square`
+    pub fn trap_on(&self, color: chess::Color, square: chess::Square) -> Option<Arc<dyn Spell>> {
+        self.casted(color).get(&(square.get_rank().to_index(), square.get_file().to_index())).map(|cast| cast.spell.clone())
+    }
+
+    fn casted(&self, color: chess::Color) -> &HashMap<(usize, usize), Cast> {
+        if color == self.player { &self.casted_mine } else { &self.casted_other }
+    }
+
+    fn casted_mut(&mut self, color: chess::Color) -> &mut HashMap<(usize, usize), Cast> {
+        if color == self.player { &mut self.casted_mine } else { &mut self.casted_other }
+    }
+
+    /// the armed copy of `spell` if casting it on `square` lays a trap: only an empty square can
+    /// hide one, cast onto a piece a trap spell goes off right away
+    pub fn trap_for(&self, spell: &dyn Spell, square: chess::Square) -> Option<Arc<dyn Spell>> {
+        self.board.piece_on(square).is_none().then(|| spell.arm(square)).flatten()
+    }
+
+    /// squares of the traps armed by `color`, which only that side gets to see
+    pub fn traps(&self, color: chess::Color) -> Vec<chess::Square> {
+        self.casted(color).values().filter_map(|cast| cast.square).collect()
     }
 
     pub fn make_move_new(&self, m: chess::ChessMove) -> Self {
@@ -55,61 +218,228 @@ impl Game {
         new_game
     }
 
-    pub fn spells(&self) -> Vec<Arc<dyn Spell>> {
-        self.inventory.spells()
+    /// the side that may act right now: the opponent of the caster while a spell is pending
+    pub fn acting_side(&self) -> chess::Color {
+        match self.pending() {
+            Some(cast) => !cast.caster,
+            None => self.raw_board().side_to_move(),
+        }
+    }
+
+    pub fn inventory(&self, color: chess::Color) -> &Inventory {
+        &self.inventories[color.to_index()]
     }
 
-    pub fn new_board(&self) -> board::UIBoard {
-        self.board.clone()
+    /// spells held by the side that may act right now
+    pub fn spells(&self) -> Vec<Arc<dyn Spell>> {
+        self.inventory(self.acting_side()).spells()
     }
 
     pub fn set_board(self, board: board::UIBoard) -> Self {
         Self {
             board,
-            moves: self.moves,
-            inventory: self.inventory,
-            casted_mine: self.casted_mine,
-            casted_other: self.casted_other,
+            ..self
         }
     }
 
-    pub fn cast_spell(&self, spell: Arc<dyn Spell>, square: chess::Square) -> Self {
-        let spell = spell.clone();
-        let i_ = self.inventory.to_owned().remove_spell(spell.identifier());//self.remove_spell(spell.identifier());
-        let mut casted_ = self.casted_mine.to_owned();
-        casted_.insert((square.get_rank().to_index(), square.get_file().to_index()), spell);
-        Self {
-            board: self.board.clone(),
-            moves: self.moves.clone(),
-            inventory: i_.clone(),
-            casted_mine: casted_,
-            casted_other: self.casted_other.clone(),
+    /// Magic FEN of the position play started from, empty for the standard start without spells in hand
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// the game as it was before anything happened, history replays from here
+    pub fn start(&self) -> Result<Game, fen::FenError> {
+        match self.origin.is_empty() {
+            true => Ok(Game::new()),
+            false => Game::from_fen(&self.origin),
         }
-        
     }
 
-    pub fn collect_spell(&self, square: chess::Square, spell: Arc<dyn Spell>) -> Game {
-        let i_ = self.inventory.clone().collect_spell(spell);
-        Self {
-            board: self.board.clone(),
-            moves: self.moves.clone(),
-            inventory: i_,
-            casted_mine: self.casted_mine.clone(),
-            casted_other: self.casted_other.clone(),
+    pub fn history(&self) -> &[history::Entry] {
+        &self.history
+    }
+
+    fn record(&mut self, ply: usize, event: history::Event) {
+        self.history.push(history::Entry { ply: self.first_ply + ply, event, board: self.board.clone() });
+    }
+
+    /// the cast waiting for the opponent's response, if any
+    pub fn pending(&self) -> Option<&Cast> {
+        self.stack.first()
+    }
+
+    /// checks the casting rules for the side to move without casting anything
+    pub fn can_cast(&self, spell: &dyn Spell) -> Result<(), CastError> {
+        let ply = self.moves.len();
+        let side = self.raw_board().side_to_move();
+        if self.pending().is_some() {
+            return match spell.speed() {
+                Speed::Instant => Ok(()),
+                Speed::Sorcery => Err(CastError::Pending),
+            };
         }
+        if spell.speed() == Speed::Instant {
+            return Err(CastError::NothingToRespondTo);
+        }
+        if self.casts_this_turn + spell.cost() > self.rules.max_casts_per_turn {
+            return Err(CastError::TooManyCasts);
+        }
+        if let Some(ready) = self.cooldowns.get(&(side, spell.identifier())) {
+            if *ready > ply {
+                return Err(CastError::OnCooldown((ready - ply).div_ceil(2)));
+            }
+        }
+        if !self.rules.cast_in_check && self.raw_board().checkers().popcnt() > 0 {
+            return Err(CastError::InCheck);
+        }
+        Ok(())
     }
 
-    pub fn remove_spell(self, id: u32) -> Self {
-        let i_ = self.inventory.remove_spell(id);
-        // let mut spells = self.spells().clone();
-        // let rm_index = spells.iter().position(|s| s.identifier() == id).unwrap();
-        // spells.remove(rm_index);
+    /// puts a spell on the stack; it only takes effect once the opponent responded or passed
+    pub fn cast_spell(&self, spell: Arc<dyn Spell>, square: chess::Square) -> Result<Self, CastError> {
+        self.can_cast(spell.as_ref())?;
+        let side = self.raw_board().side_to_move();
+        if !self.inventory(side).holds(&spell) {
+            return Err(CastError::NotHeld);
+        }
+        let mut inventories = self.inventories.to_owned();
+        inventories[side.to_index()] = inventories[side.to_index()].to_owned().remove_spell(&spell);
+        let mut cooldowns = self.cooldowns.to_owned();
+        cooldowns.insert((side, spell.identifier()), self.rules.ready_at(self.moves.len()));
+        let mut stack = self.stack.to_owned();
+        stack.push(Cast::new(spell.clone(), Some(square), side));
+        let mut game = Self {
+            inventories,
+            casts_this_turn: self.casts_this_turn + spell.cost(),
+            cooldowns,
+            stack,
+            ..self.clone()
+        };
+        let hidden = self.trap_for(spell.as_ref(), square).is_some();
+        game.record(self.moves.len(), history::Event::Cast { caster: side, spell, square: Some(square), hidden });
+        Ok(game)
+    }
+
+    /// answers the pending cast with an instant spell and resolves the stack
+    pub fn respond(&self, spell: Arc<dyn Spell>) -> Result<Self, CastError> {
+        let side = match self.pending() {
+            Some(cast) => !cast.caster,
+            None => return Err(CastError::NothingToRespondTo),
+        };
+        if spell.speed() != Speed::Instant {
+            return Err(CastError::Pending);
+        }
+        if !self.inventory(side).holds(&spell) {
+            return Err(CastError::NotHeld);
+        }
+        let mut inventories = self.inventories.to_owned();
+        inventories[side.to_index()] = inventories[side.to_index()].to_owned().remove_spell(&spell);
+        let mut stack = self.stack.to_owned();
+        stack.push(Cast::new(spell.clone(), None, side));
+        let mut game = Self {
+            inventories,
+            stack,
+            ..self.clone()
+        };
+        game.record(self.moves.len(), history::Event::Respond { by: side, spell });
+        Ok(game.resolve())
+    }
+
+    /// resolves the stack top down, each instant acting on the cast beneath it
+    pub fn resolve(&self) -> Self {
+        let mut game = self.clone();
+        let mut casts = std::mem::take(&mut game.stack);
+        let mut reaction: Option<Cast> = None;
+        while let Some(cast) = casts.pop() {
+            let cast = match reaction.take() {
+                Some(r) => match r.spell.respond(cast, r.caster) {
+                    Some(cast) => cast,
+                    None => continue,
+                },
+                None => cast,
+            };
+            match cast.spell.speed() {
+                Speed::Instant => reaction = Some(cast),
+                Speed::Sorcery => game = game.execute_cast(cast, self.moves.len()),
+            }
+        }
+        game
+    }
+
+    fn execute_cast(self, cast: Cast, ply: usize) -> Self {
+        match cast.square.and_then(|square| self.trap_for(cast.spell.as_ref(), square).map(|armed| (square, armed))) {
+            Some((square, armed)) => {
+                let mut game = self;
+                let expires_at = armed.duration().map(|turns| ply + 2 * turns as usize);
+                game.casted_mut(cast.caster).insert((square.get_rank().to_index(), square.get_file().to_index()), Cast {
+                    spell: armed,
+                    expires_at,
+                    ..cast
+                });
+                game
+            },
+            None => self.detonate(cast, ply),
+        }
+    }
+
+    fn detonate(self, cast: Cast, ply: usize) -> Self {
+        let shielded = Self {
+            shielded: cast.shielded,
+            ..self
+        };
+        let mut game = cast.spell.execute(shielded, cast.square);
+        game.record(ply, history::Event::Effect { spell: cast.spell.clone(), square: cast.square });
         Self {
-            board: self.board.clone(),
-            moves: self.moves.clone(),
-            inventory: i_,
-            casted_mine: self.casted_mine.clone(),
-            casted_other: self.casted_other.clone(),
+            shielded: None,
+            ..game
+        }
+    }
+
+    /// removes the pieces on the given squares, sparing a shielded side, kings, and pieces
+    /// whose removal would leave the side not to move in check
+    pub fn clear_squares(self, squares: &[chess::Square]) -> Self {
+        let mut board = self.board.clone();
+        let mut left = squares.iter()
+            .filter(|square| board.piece_on(**square).is_some())
+            .filter(|square| self.shielded.is_none() || self.raw_board().color_on(**square) != self.shielded)
+            .copied()
+            .collect::<Vec<_>>();
+        // a piece that shields a king may come off once the piece attacking it is gone too
+        loop {
+            let before = left.len();
+            left.retain(|square| match board.remove_piece(*square) {
+                Ok(cleared) => {
+                    board = cleared;
+                    false
+                },
+                Err(_) => true,
+            });
+            if left.len() == before {
+                break;
+            }
         }
+        for square in left {
+            if let Err(e) = board.remove_piece(square) {
+                info!("{}", e);
+            }
+        }
+        self.set_board(board)
     }
-}
\ No newline at end of file
+
+    /// hands a spell to whoever owns the piece standing on `square`
+    pub fn collect_spell(&self, square: chess::Square, spell: Arc<dyn Spell>) -> Game {
+        let color = match self.raw_board().color_on(square) {
+            Some(color) => color,
+            None => return self.clone(),
+        };
+        let mut inventories = self.inventories.to_owned();
+        inventories[color.to_index()] = inventories[color.to_index()].to_owned().collect_spell(spell.clone());
+        let mut game = Self {
+            board: self.board.take_spell(square),
+            inventories,
+            ..self.clone()
+        };
+        game.record(self.moves.len().saturating_sub(1), history::Event::Pickup { by: color, spell, square });
+        game
+    }
+}
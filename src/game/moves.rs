@@ -1,16 +1,15 @@
-use chess::{Board, Square, ChessMove};
-use web_sys::{Document, HtmlElement};
-use web_sys::console::info;
-use yew::{DragEvent, MouseEvent, TargetCast, HtmlResult, UseStateHandle};
+use chess::{Square, Piece};
+use yew::{DragEvent, TargetCast};
 use std::sync::Arc;
-use std::{result::Result, ops::Div};
+use std::result::Result;
 use log::info;
 use crate::document;
 
-use super::board::render::UIBoard;
-use super::{Game, Spell, spell};
+use super::board::{parse_square, square_name, View};
+use super::{Game, Spell};
 
 /// Error Type for Illagl Move
+#[derive(Debug)]
 pub struct IllegalMoveError;
 
 impl std::fmt::Display for IllegalMoveError {
@@ -19,24 +18,47 @@ impl std::fmt::Display for IllegalMoveError {
     }
 }
 
-pub fn move_piece(game: &Game, target_square: Square, source_id: String) -> Result<Game, IllegalMoveError> {
-    let r = source_id.chars().nth(0).unwrap().to_digit(10).unwrap();
-    let f = source_id.chars().nth(1).unwrap().to_digit(10).unwrap();
-    info!("from -> file: {}, rank: {}", f, r);
-    let source_square = Square::make_square(chess::Rank::from_index(f as usize), chess::File::from_index(r as usize));
-    let m = ChessMove::new(source_square, target_square, None);
-    info!("move: {:?}", m);
+/// plays a move from `source_square` to `target_square`, a pawn reaching the last rank becomes a queen
+pub fn move_piece(game: &Game, source_square: Square, target_square: Square) -> Result<Game, IllegalMoveError> {
+    if game.pending().is_some() {
+        return Err(IllegalMoveError);
+    }
+    info!("move: {}{}", square_name(source_square), square_name(target_square));
     let mut legal = chess::MoveGen::new_legal(game.raw_board());
 
-    match legal.find(|l| l == &m) {
+    match legal.find(|l| {
+        l.get_source() == source_square && l.get_dest() == target_square && matches!(l.get_promotion(), None | Some(Piece::Queen))
+    }) {
         Some(m) => Ok(game.make_move_new(m)),
         None => Err(IllegalMoveError),
     }
 }
 
+/// squares the piece on `source_square` can legally move to
+pub fn legal_targets(game: &Game, source_square: Square) -> Vec<Square> {
+    if game.pending().is_some() {
+        return Vec::new();
+    }
+    chess::MoveGen::new_legal(game.raw_board())
+        .filter(|m| m.get_source() == source_square)
+        .map(|m| m.get_dest())
+        .collect()
+}
+
+/// parses the "piece-{square}" id of a piece image
+fn parse_id(source_id: &str) -> Option<Square> {
+    let square = parse_square(source_id.strip_prefix("piece-")?)?;
+    info!("from -> {}", square_name(square));
+    Some(square)
+}
+
+/// moves a piece and picks up whatever spell lies on its target square
+pub fn play(game: &Game, source_square: Square, target_square: Square) -> Result<Game, IllegalMoveError> {
+    let game = move_piece(game, source_square, target_square)?;
+    Ok(collect_spell(&game, target_square))
+}
+
 pub fn collect_spell(game: &Game, target_square: Square) -> Game {
-    let piece = game.board().piece_on(target_square);
-    let color = game.board().color_on(target_square);
     let spell = game.board().spell_on(target_square);
     match spell {
         Some(s) => game.collect_spell(target_square, s),
@@ -48,73 +70,110 @@ pub fn make_move(game: &Game, event: DragEvent) -> Game {
     info!("make_move called");
     let point = (event.client_x() as f64, event.client_y() as f64);
     let e: Option<web_sys::HtmlElement> = event.target_dyn_into();
-    let board_el = document().get_element_by_id(&"board").unwrap();
 
     let id = match e {
         Some(e) => e.id(),
         None => return game.clone(),
     };
 
-    match id.len() {
-        0 => return {
+    let source_square = match parse_id(&id) {
+        Some(square) => square,
+        None => {
             info!{"no piece target"};
-            game.clone()
-        },
-        _ => {
-            info!{"target id: {}", id};
-            let target_square = map_to_square(point, &board_el, game.board().dims());
-            let game = match move_piece(game, target_square, id) {
-                Ok(game) => game,
-                Err(e) => {
-                    return game.clone();
-                }
-            };
-            let game = collect_spell(&game, target_square);
             return game.clone();
         }
     };
-    //board.make_move(from, to);
+    info!{"target id: {}", id};
+    drop_piece(game, source_square, point)
 }
 
+/// plays the piece on `source_square` to the square under the client coordinates `point`
+pub fn drop_piece(game: &Game, source_square: Square, point: (f64, f64)) -> Game {
+    let board_el = document().get_element_by_id("board").unwrap();
+    land_piece(game, source_square, map_to_square(point, &board_el, game.view()))
+}
+
+/// the game after letting go of the piece on `source_square` over `target`, unchanged off the board or on an illegal square
+fn land_piece(game: &Game, source_square: Square, target: Option<Square>) -> Game {
+    let target_square = match target {
+        Some(square) => square,
+        None => return game.clone(),
+    };
+    match play(game, source_square, target_square) {
+        Ok(game) => game,
+        Err(e) => {
+            info!("{}", e);
+            game.clone()
+        }
+    }
+}
 
 pub fn cast_spell(game: &Game, spell: Arc<dyn Spell>, event: DragEvent) -> Game {
     info!("cast_spell called");
     let point = (event.client_x() as f64, event.client_y() as f64);
-    let e: Option<web_sys::HtmlElement> = event.target_dyn_into();
-    let board_el = document().get_element_by_id(&"board").unwrap();
-
-    let square = map_to_square(point, &board_el, game.board().dims());
-    //let valid = game.board().piece_on(square).unwrap_or(false).is_mine();
-    info!("casted {:?} to square: {:?}", spell.name(), square);
+    drop_spell(game, spell, point)
+}
 
-    let game = spell.execute(game.clone(), Some(square));
+/// casts `spell` onto the square under the client coordinates `point`
+pub fn drop_spell(game: &Game, spell: Arc<dyn Spell>, point: (f64, f64)) -> Game {
+    let board_el = document().get_element_by_id("board").unwrap();
+    land_spell(game, spell, map_to_square(point, &board_el, game.view()))
+}
 
-    let id = match e {
-        Some(e) => e.id(),
+/// the game after letting go of `spell` over `target`, unchanged off the board or where it can't be cast
+fn land_spell(game: &Game, spell: Arc<dyn Spell>, target: Option<Square>) -> Game {
+    let square = match target {
+        Some(square) => square,
         None => return game.clone(),
     };
+    info!("casted {} to square: {}", spell.name(), square_name(square));
 
-    match id.len() {
-        0 => return {
-            info!{"no piece target"};
+    match game.cast_spell(spell, square) {
+        Ok(game) => game,
+        Err(e) => {
+            info!("{}", e);
             game.clone()
-        },
-        _ => {
-            info!{"target id: {}", id};
         }
-    };
+    }
+}
 
-    game.clone()
+/// click-to-move: the first click selects one of the mover's pieces, the second one moves it
+pub fn select(game: &Game, selected: Option<Square>, square: Square) -> (Game, Option<Square>) {
+    let own_piece = game.raw_board().color_on(square) == Some(game.raw_board().side_to_move());
+    match selected {
+        Some(source) if source != square && !own_piece => match play(game, source, square) {
+            Ok(game) => (game, None),
+            Err(e) => {
+                info!("{}", e);
+                (game.clone(), None)
+            }
+        },
+        Some(source) if source == square => (game.clone(), None),
+        _ if own_piece => (game.clone(), Some(square)),
+        _ => (game.clone(), None),
+    }
+}
+
+pub fn respond(game: &Game, spell: Arc<dyn Spell>) -> Game {
+    info!("{:?} responds with {:?}", game.acting_side(), spell.name());
+    match game.respond(spell) {
+        Ok(game) => game,
+        Err(e) => {
+            info!("{}", e);
+            game.clone()
+        }
+    }
 }
 
-fn map_to_square(point: (f64, f64), board_el: &web_sys::Element, dims: (usize, usize)) -> Square {
+fn map_to_square(point: (f64, f64), board_el: &web_sys::Element, view: View) -> Option<Square> {
     let board_bounds = board_el.get_bounding_client_rect();
     let board_pos = (board_bounds.x(), board_bounds.y());
     let board_size = (board_el.client_width() as f64, board_el.client_height() as f64);
-    map_to_square_(point, board_size, board_pos, dims)
+    map_to_square_(point, board_size, board_pos, view)
 }
 
-fn map_to_square_(point: (f64, f64), board_size: (f64, f64), board_pos: (f64, f64), board_dims: (usize, usize)) -> Square {
+fn map_to_square_(point: (f64, f64), board_size: (f64, f64), board_pos: (f64, f64), view: View) -> Option<Square> {
+    let board_dims = view.dims();
     let square_dims = (board_size.0 / board_dims.0 as f64, board_size.1 / board_dims.1 as f64);
     info!("square_dims: {:?}", square_dims);
     info!("board_pos: {:?}", board_pos);
@@ -125,17 +184,103 @@ fn map_to_square_(point: (f64, f64), board_size: (f64, f64), board_pos: (f64, f6
     info!("normalized_point: {:?}", normalized_point);
     info!("rel_pos: {:?}", ((normalized_point.0 / board_size.0), (normalized_point.1 / board_size.1)));
 
-    let inv_file = (((normalized_point.0 / board_size.0) * board_dims.0 as f64).floor() as u8);
-    let inv_rank = (((normalized_point.1 / board_size.1) * board_dims.1 as f64).floor() as u8);
+    // dropped next to the board
+    if normalized_point.0 < 0.0 || normalized_point.1 < 0.0 || normalized_point.0 >= board_size.0 || normalized_point.1 >= board_size.1 {
+        return None;
+    }
+
+    let col = ((normalized_point.0 / board_size.0) * board_dims.0 as f64).floor() as usize;
+    let row = ((normalized_point.1 / board_size.1) * board_dims.1 as f64).floor() as usize;
 
-    info!("inv_file: {}, inv_rank: {}", inv_file, inv_rank);
+    info!("col: {}, row: {}", col, row);
 
-    let file = (7 as u8) - inv_file;
-    let rank = (7 as u8) - inv_rank;
+    let (file, rank) = view.coords_of(col, row);
 
-    info!("to -> file: {}, rank: {}", file, rank);
-    Square::make_square(chess::Rank::from_index(rank as usize), chess::File::from_index(file as usize))
+    let square = Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file));
+    info!("to -> {}", square_name(square));
+    Some(square)
 }
 // ideas: invisibility spell, teleportation spell, lightning spell, jihadi warrior spell,
 //        train spell, floor is lava spell, add row spell, add column spell, poison spell, steel spell
 // add powerups to specific squares that maybe are suboptimal to get to
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::square;
+
+    #[test]
+    fn pawns_promote_to_queens() {
+        let game = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let game = move_piece(&game, square("a7"), square("a8")).unwrap();
+        assert_eq!(game.raw_board().piece_on(square("a8")), Some(Piece::Queen));
+    }
+
+    #[test]
+    fn clicks_select_own_pieces_only() {
+        let game = Game::new();
+        assert_eq!(select(&game, None, square("e2")).1, Some(square("e2")));
+        assert_eq!(select(&game, None, square("e7")).1, None);
+        assert_eq!(select(&game, None, square("e4")).1, None);
+    }
+
+    #[test]
+    fn clicks_reselect_and_deselect() {
+        let game = Game::new();
+        assert_eq!(select(&game, Some(square("e2")), square("d2")).1, Some(square("d2")));
+        let (after, selected) = select(&game, Some(square("e2")), square("e2"));
+        assert_eq!((after.raw_board(), selected), (game.raw_board(), None));
+    }
+
+    #[test]
+    fn second_clicks_move_and_capture() {
+        let game = Game::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let (moved, selected) = select(&game, Some(square("e4")), square("e5"));
+        assert_eq!((moved.raw_board().piece_on(square("e5")), selected), (Some(Piece::Pawn), None));
+        let (captured, _) = select(&game, Some(square("e4")), square("d5"));
+        assert_eq!(captured.raw_board().color_on(square("d5")), Some(chess::Color::White));
+        // an illegal target drops the selection and leaves the game as it was
+        let (same, selected) = select(&game, Some(square("e4")), square("e6"));
+        assert_eq!((same.raw_board(), selected), (game.raw_board(), None));
+    }
+
+    #[test]
+    fn points_map_to_squares_for_either_side() {
+        let (size, pos) = ((400.0, 400.0), (100.0, 50.0));
+        let white = View::new((8, 8), chess::Color::White);
+        let black = View::new((8, 8), chess::Color::Black);
+        // just inside the top left corner
+        assert_eq!(map_to_square_((101.0, 51.0), size, pos, white), Some(square("a8")));
+        assert_eq!(map_to_square_((101.0, 51.0), size, pos, black), Some(square("h1")));
+        assert_eq!(map_to_square_((499.0, 449.0), size, pos, black), Some(square("a8")));
+        assert_eq!(map_to_square_((99.0, 51.0), size, pos, white), None);
+        assert_eq!(map_to_square_((500.0, 51.0), size, pos, white), None);
+    }
+
+    #[test]
+    fn points_map_to_squares_on_smaller_boards() {
+        let view = View::new((4, 2), chess::Color::White);
+        let (size, pos) = ((400.0, 200.0), (0.0, 0.0));
+        assert_eq!(map_to_square_((10.0, 10.0), size, pos, view), Some(square("a2")));
+        assert_eq!(map_to_square_((390.0, 190.0), size, pos, view), Some(square("d1")));
+    }
+
+    #[test]
+    fn dropped_pieces_move_where_they_land() {
+        let game = Game::new();
+        let moved = land_piece(&game, square("e2"), Some(square("e4")));
+        assert_eq!(moved.raw_board().piece_on(square("e4")), Some(Piece::Pawn));
+        // let go next to the board or on a square the piece can't reach
+        assert_eq!(land_piece(&game, square("e2"), None).raw_board(), game.raw_board());
+        assert_eq!(land_piece(&game, square("e2"), Some(square("e5"))).raw_board(), game.raw_board());
+    }
+
+    #[test]
+    fn dropped_spells_are_cast_where_they_land() {
+        let game = Game::new().with_inventories([crate::game::Inventory::test(), crate::game::Inventory::test()]);
+        let warrior = game.spells()[0].clone();
+        assert!(land_spell(&game, warrior.clone(), None).pending().is_none());
+        let cast = land_spell(&game, warrior, Some(square("e5")));
+        assert_eq!(cast.pending().map(|cast| cast.square), Some(Some(square("e5"))));
+    }
+}
@@ -0,0 +1,117 @@
+use chess::{Board, BoardStatus, ChessMove, MoveGen, Piece};
+
+use super::board::{file_name, square_name};
+
+fn piece_letter(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "",
+        Piece::Knight => "N",
+        Piece::Bishop => "B",
+        Piece::Rook => "R",
+        Piece::Queen => "Q",
+        Piece::King => "K",
+    }
+}
+
+/// Standard Algebraic Notation of a legal move `m` played on `board`
+pub fn san(board: &Board, m: ChessMove) -> String {
+    let (source, dest) = (m.get_source(), m.get_dest());
+    let piece = match board.piece_on(source) {
+        Some(piece) => piece,
+        None => return m.to_string(),
+    };
+    let file_distance = (source.get_file().to_index() as i32 - dest.get_file().to_index() as i32).abs();
+
+    let mut text = if piece == Piece::King && file_distance == 2 {
+        match dest.get_file() {
+            chess::File::G => "O-O".to_owned(),
+            _ => "O-O-O".to_owned(),
+        }
+    } else {
+        let capture = board.piece_on(dest).is_some() || (piece == Piece::Pawn && source.get_file() != dest.get_file());
+        let mut text = piece_letter(piece).to_owned();
+        if piece == Piece::Pawn {
+            if capture {
+                text.push(file_name(source.get_file().to_index()));
+            }
+        } else {
+            // other pieces of the same kind that could go to the same square
+            let rivals = MoveGen::new_legal(board)
+                .filter(|o| o.get_dest() == dest && o.get_source() != source && board.piece_on(o.get_source()) == Some(piece))
+                .map(|o| o.get_source())
+                .collect::<Vec<_>>();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|r| r.get_file() != source.get_file()) {
+                    text.push(file_name(source.get_file().to_index()));
+                } else if rivals.iter().all(|r| r.get_rank() != source.get_rank()) {
+                    text.push_str(&(source.get_rank().to_index() + 1).to_string());
+                } else {
+                    text.push_str(&square_name(source));
+                }
+            }
+        }
+        if capture {
+            text.push('x');
+        }
+        text.push_str(&square_name(dest));
+        if let Some(promotion) = m.get_promotion() {
+            text.push('=');
+            text.push_str(piece_letter(promotion));
+        }
+        text
+    };
+
+    let after = board.make_move_new(m);
+    if after.status() == BoardStatus::Checkmate {
+        text.push('#');
+    } else if after.checkers().popcnt() > 0 {
+        text.push('+');
+    }
+    text
+}
+
+/// move number prefix of the ply `ply`, "12." for white and "12..." for black
+pub fn move_number(ply: usize) -> String {
+    match ply % 2 {
+        0 => format!("{}.", ply / 2 + 1),
+        _ => format!("{}...", ply / 2 + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::Color;
+
+    use super::*;
+    use crate::game::moves::play;
+    use crate::game::testing::square;
+    use crate::game::Game;
+
+    fn san_of(fen: &str, m: &str) -> String {
+        let board = Board::from_str(fen).unwrap();
+        san(&board, ChessMove::from_str(m).unwrap())
+    }
+
+    #[test]
+    fn writes_san() {
+        assert_eq!(san_of("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"), "O-O");
+        assert_eq!(san_of("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1"), "O-O-O");
+        assert_eq!(san_of("4k3/8/8/8/8/8/8/1N1NK3 w - - 0 1", "b1c3"), "Nbc3");
+        assert_eq!(san_of("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(san_of("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"), "axb8=Q+");
+        assert_eq!(san_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+        assert_eq!(san_of("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn numbers_moves_from_the_starting_position() {
+        let game = Game::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 12").unwrap();
+        let game = play(&game, square("e7"), square("e5")).unwrap();
+        let game = play(&game, square("g1"), square("f3")).unwrap();
+        let labels = game.history().iter().map(|entry| entry.label(Color::White)).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["12... e5", "13. Nf3"]);
+        assert_eq!(game.history()[0].describe(Color::White), "Black plays e5 (e7e5)");
+    }
+}
@@ -0,0 +1,306 @@
+//! PGN export and import. Moves are plain SAN so any chess tool can read the game; spell events
+//! travel in comments as commands, e.g. `{[%cast b W2@e5]}` or `{[%respond w R]}` with spells
+//! written as in Magic FEN, and the starting magic position in a `MagicFEN` tag. Hints taken by
+//! the end of the game are kept in a `Hints` tag as `white/black`.
+//!
+//! Only casts and responses are replayed on import, effects and pickups follow from them and are
+//! exported for readers only. A cast left unanswered resolves before the next move or cast.
+use std::str::FromStr;
+
+use chess::{Board, BoardStatus, ChessMove, Color, Square};
+
+use super::ai::SpellId;
+use super::board::{color_char, parse_square, square_name};
+use super::fen::FenError;
+use super::history::{Event, Recorded};
+use super::notation::move_number;
+use super::{from_spell_token, spell_token, Game, Spell};
+
+/// Error Type for PGN that can't be replayed
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgnError {
+    Position(FenError),
+    Move(String),
+    Spell(String),
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PgnError::Position(e) => write!(f, "{}", e),
+            PgnError::Move(san) => write!(f, "Illegal move {}", san),
+            PgnError::Spell(command) => write!(f, "Can't replay {}", command),
+        }
+    }
+}
+
+fn at(square: Option<Square>) -> String {
+    square.map(|square| format!("@{}", square_name(square))).unwrap_or_default()
+}
+
+/// the command comment for a spell event, `None` for moves
+fn command(event: &Event) -> Option<String> {
+    let letter = |spell: &dyn Spell| spell_token(spell);
+    match event {
+        Event::Move { .. } => None,
+        Event::Cast { caster, spell, square, .. } => Some(format!("[%cast {} {}{}]", color_char(*caster), letter(spell.as_ref()), at(*square))),
+        Event::Respond { by, spell } => Some(format!("[%respond {} {}]", color_char(*by), letter(spell.as_ref()))),
+        Event::Effect { spell, square } => Some(format!("[%effect {}{}]", letter(spell.as_ref()), at(*square))),
+        Event::Pickup { by, spell, square } => Some(format!("[%pickup {} {}@{}]", color_char(*by), letter(spell.as_ref()), square_name(*square))),
+    }
+}
+
+fn result(game: &Game) -> &'static str {
+    match game.raw_board().status() {
+        BoardStatus::Checkmate => match game.raw_board().side_to_move() {
+            Color::White => "0-1",
+            Color::Black => "1-0",
+        },
+        BoardStatus::Stalemate => "1/2-1/2",
+        BoardStatus::Ongoing => "*",
+    }
+}
+
+/// A piece of movetext
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    San(String),
+}
+
+/// splits PGN into tags, comments and moves, dropping numbers, results, NAGs and variations
+fn tokenize(pgn: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pgn.chars().peekable();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '[' if depth == 0 => {
+                let tag = chars.by_ref().take_while(|c| *c != ']').collect::<String>();
+                if let Some((name, value)) = tag.split_once(' ') {
+                    tokens.push(Token::Tag(name.to_owned(), value.trim().trim_matches('"').to_owned()));
+                }
+            },
+            '{' => {
+                let comment = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                if depth == 0 {
+                    tokens.push(Token::Comment(comment));
+                }
+            },
+            ';' => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            },
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c.is_whitespace() => {},
+            c => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.next_if(|c| !c.is_whitespace() && !"{}()[;".contains(*c)) {
+                    word.push(next);
+                }
+                // SAN never contains a dot, so everything up to the last one is the move number
+                let word = match word.rfind('.') {
+                    Some(dot) => word[dot + 1..].to_owned(),
+                    None => word,
+                };
+                let skip = word.is_empty() || word.starts_with('$') || ["1-0", "0-1", "1/2-1/2", "*"].contains(&word.as_str());
+                if depth == 0 && !skip {
+                    tokens.push(Token::San(word));
+                }
+            },
+        }
+    }
+    tokens
+}
+
+/// the `[%cast ...]` and `[%respond ...]` commands of a comment, anything else is left alone
+fn commands(comment: &str) -> Result<Vec<Recorded<String>>, PgnError> {
+    let invalid = || PgnError::Spell(comment.trim().to_owned());
+    let spell = |token: &str| from_spell_token(token).map(|spell| SpellId::of(spell.as_ref())).ok_or_else(invalid);
+    let mut records = Vec::new();
+    for command in comment.split('[').filter_map(|part| part.strip_prefix('%')) {
+        let words = command.trim_end().trim_end_matches(']').split_whitespace().collect::<Vec<_>>();
+        if !matches!(words.get(1), Some(&"w") | Some(&"b")) {
+            continue;
+        }
+        match (words[0], words.get(2)) {
+            ("cast", Some(token)) => {
+                let (token, square) = token.split_once('@').ok_or_else(invalid)?;
+                records.push(Recorded::Cast(spell(token)?, parse_square(square).ok_or_else(invalid)?));
+            },
+            ("respond", Some(token)) => records.push(Recorded::Respond(spell(token)?)),
+            _ => {},
+        }
+    }
+    Ok(records)
+}
+
+/// the legal move `san` stands for on `board`
+fn read_san(board: &Board, san: &str) -> Option<ChessMove> {
+    let text = san.trim_end_matches(['+', '#', '!', '?']);
+    // castling written with zeros
+    let text = match text.chars().all(|c| c == '0' || c == '-') {
+        true => text.replace('0', "O"),
+        false => text.to_owned(),
+    };
+    ChessMove::from_san(board, &text).ok().filter(|m| board.legal(*m))
+}
+
+impl Game {
+    /// the game as PGN with its spells in comments, see the `pgn` module
+    pub fn to_pgn(&self) -> String {
+        let result = result(self);
+        let mut tags = vec![
+            ("Event", "Magic Chess".to_owned()),
+            ("Site", "?".to_owned()),
+            ("Date", "????.??.??".to_owned()),
+            ("Round", "-".to_owned()),
+            ("White", "White".to_owned()),
+            ("Black", "Black".to_owned()),
+            ("Result", result.to_owned()),
+        ];
+        if !self.origin.is_empty() {
+            let standard = self.origin.split(' ').take(6).collect::<Vec<_>>().join(" ");
+            if Board::from_str(&standard).ok().as_ref() != Some(Game::default().raw_board()) {
+                tags.push(("SetUp", "1".to_owned()));
+                tags.push(("FEN", standard));
+            }
+            tags.push(("MagicFEN", self.origin.clone()));
+        }
+        if self.hints != [0, 0] {
+            tags.push(("Hints", format!("{}/{}", self.hints[0], self.hints[1])));
+        }
+
+        let mut movetext = Vec::new();
+        // black's moves only get a number of their own when something came between them and white's
+        let mut after_white = false;
+        for entry in &self.history {
+            match (&entry.event, command(&entry.event)) {
+                (Event::Move { san, .. }, _) => {
+                    match (entry.ply % 2, after_white) {
+                        (0, _) => movetext.push(format!("{} {}", move_number(entry.ply).trim_end(), san)),
+                        (_, true) => movetext.push(san.clone()),
+                        (_, false) => movetext.push(format!("{} {}", move_number(entry.ply).trim_end(), san)),
+                    }
+                    after_white = entry.ply % 2 == 0;
+                },
+                (_, Some(command)) => {
+                    movetext.push(format!("{{{}}}", command));
+                    after_white = false;
+                },
+                (_, None) => {},
+            }
+        }
+        movetext.push(result.to_owned());
+
+        let tags = tags.iter().map(|(name, value)| format!("[{} \"{}\"]", name, value)).collect::<Vec<_>>().join("\n");
+        format!("{}\n\n{}\n", tags, movetext.join(" "))
+    }
+
+    /// replays PGN written by `to_pgn`, or plain PGN without any spells
+    pub fn from_pgn(pgn: &str) -> Result<Self, PgnError> {
+        let tokens = tokenize(pgn);
+        let hints = tokens.iter().find_map(|token| match token {
+            Token::Tag(name, value) if name == "Hints" => Some(value.clone()),
+            _ => None,
+        });
+        let start = tokens.iter().fold((None, None), |(magic, fen), token| match token {
+            Token::Tag(name, value) if name == "MagicFEN" => (Some(value.clone()), fen),
+            Token::Tag(name, value) if name == "FEN" => (magic, Some(value.clone())),
+            _ => (magic, fen),
+        });
+        let start = match start {
+            (Some(fen), _) | (None, Some(fen)) => Game::from_fen(&fen).map_err(PgnError::Position)?,
+            (None, None) => Game::new(),
+        };
+        // what to report for each record if it doesn't replay
+        let mut records = Vec::new();
+        let mut errors = Vec::new();
+        for token in tokens {
+            match token {
+                Token::Tag(..) => {},
+                Token::Comment(comment) => for record in commands(&comment)? {
+                    records.push(record);
+                    errors.push(PgnError::Spell(comment.trim().to_owned()));
+                },
+                Token::San(san) => {
+                    errors.push(PgnError::Move(san.clone()));
+                    records.push(Recorded::Move(san));
+                },
+            }
+        }
+        let mut steps = start.replay(records, |board, san| read_san(board, san))
+            .map_err(|i| errors.swap_remove(i))?;
+        let mut game = steps.pop().map_or(start, |step| step.game);
+        if let Some(hints) = hints {
+            let invalid = || PgnError::Position(FenError::Counter(hints.clone()));
+            let (white, black) = hints.split_once('/').ok_or_else(invalid)?;
+            game.hints = [white.parse().map_err(|_| invalid())?, black.parse().map_err(|_| invalid())?];
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{opening, square};
+
+    #[test]
+    fn plain_pgn_replays() {
+        let pgn = "[Event \"Casual\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6?? 4. Qxf7# 1-0";
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.raw_board().status(), BoardStatus::Checkmate);
+        assert!(game.to_pgn().ends_with("1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"));
+    }
+
+    #[test]
+    fn spells_survive_a_round_trip() {
+        let game = opening();
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("1. d4 {[%pickup w R@d4]} {[%cast b W@e5]} 1... e6"));
+        assert_eq!(Game::from_pgn(&pgn).unwrap().to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn castling_may_be_written_with_zeros() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let game = Game::from_pgn(&format!("[FEN \"{}\"]\n\n1. 0-0-0 0-0 *", fen)).unwrap();
+        assert!(game.to_fen().starts_with("r4rk1/8/8/8/8/8/8/2KR3R w - - 2 2"));
+    }
+
+    #[test]
+    fn numbers_moves_from_the_starting_position() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 3 12").unwrap();
+        let game = game.make_move_new("e8d8".parse().unwrap()).make_move_new("e1g1".parse().unwrap());
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[FEN \"4k3/8/8/8/8/8/8/4K2R b K - 3 12\"]"));
+        assert!(pgn.ends_with("12... Kd8 13. O-O *\n"));
+        assert_eq!(Game::from_pgn(&pgn).unwrap().to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn casts_name_the_level_cast() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 - W3W/- - 0 -").unwrap();
+        let weak = game.spells()[1].clone();
+        let game = game.cast_spell(weak, square("e5")).unwrap();
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("{[%cast w W@e5]}"));
+        let replayed = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(replayed.inventory(Color::White).spells()[0].level(), 3);
+    }
+
+    #[test]
+    fn hints_taken_are_kept() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 - -/- - 0 - 1/0").unwrap();
+        let game = game.make_move_new("e1d1".parse().unwrap());
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Hints \"1/0\"]"));
+        assert_eq!(Game::from_pgn(&pgn).unwrap().hints_left(Color::White), 2);
+    }
+
+    #[test]
+    fn illegal_moves_are_reported() {
+        assert_eq!(Game::from_pgn("1. e5").unwrap_err(), PgnError::Move("e5".to_owned()));
+    }
+}
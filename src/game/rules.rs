@@ -0,0 +1,94 @@
+/// Limits on when and how often spells can be cast
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CastingRules {
+    /// how many spells the side to move may cast before making its move
+    pub max_casts_per_turn: u8,
+    /// how many of the caster's own turns a spell kind stays unavailable after being cast
+    pub cooldown: u8,
+    /// whether spells may be cast while the side to move is in check
+    pub cast_in_check: bool,
+}
+
+impl Default for CastingRules {
+    fn default() -> Self {
+        Self {
+            max_casts_per_turn: 1,
+            cooldown: 1,
+            cast_in_check: false,
+        }
+    }
+}
+
+impl CastingRules {
+    /// ply at which a spell cast at `ply` becomes available to the same side again
+    pub fn ready_at(&self, ply: usize) -> usize {
+        ply + 2 * (self.cooldown as usize + 1)
+    }
+}
+
+/// Error Type for a spell that can't be cast right now
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CastError {
+    TooManyCasts,
+    OnCooldown(usize),
+    InCheck,
+    Pending,
+    NothingToRespondTo,
+    NotHeld,
+}
+
+impl std::fmt::Display for CastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CastError::TooManyCasts => write!(f, "No casts left this turn"),
+            CastError::OnCooldown(turns) => write!(f, "On cooldown for {} more turn(s)", turns),
+            CastError::InCheck => write!(f, "Can't cast while in check"),
+            CastError::Pending => write!(f, "Waiting for the opponent to respond"),
+            CastError::NothingToRespondTo => write!(f, "Only castable in response to a spell"),
+            CastError::NotHeld => write!(f, "That spell isn't in hand"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::square;
+    use crate::game::{Game, Inventory, JihadiWarrior};
+
+    fn white_holds_a_warrior(rules: CastingRules) -> Game {
+        Game::with_rules(rules).with_inventories([Inventory::test(), Inventory::default()])
+    }
+
+    #[test]
+    fn casts_per_turn_are_limited() {
+        let game = white_holds_a_warrior(CastingRules::default());
+        let warrior = game.spells()[0].clone();
+        let game = game.cast_spell(warrior, square("e5")).unwrap().resolve();
+        assert_eq!(game.casts_left(), 0);
+        assert_eq!(game.can_cast(&JihadiWarrior::default()), Err(CastError::TooManyCasts));
+    }
+
+    #[test]
+    fn cast_spells_cool_down() {
+        let game = white_holds_a_warrior(CastingRules::default());
+        let warrior = game.spells()[0].clone();
+        let mut game = game.cast_spell(warrior, square("e5")).unwrap().resolve();
+        for m in ["a2a3", "a7a6"] {
+            game = game.make_move_new(m.parse().unwrap());
+        }
+        assert_eq!(game.can_cast(&JihadiWarrior::default()), Err(CastError::OnCooldown(1)));
+        for m in ["b2b3", "b7b6"] {
+            game = game.make_move_new(m.parse().unwrap());
+        }
+        assert_eq!(game.can_cast(&JihadiWarrior::default()), Ok(()));
+    }
+
+    #[test]
+    fn no_casting_in_check_unless_allowed() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1 - W/- - 0 -").unwrap();
+        assert_eq!(game.can_cast(&JihadiWarrior::default()), Err(CastError::InCheck));
+        let lenient = white_holds_a_warrior(CastingRules { cast_in_check: true, ..CastingRules::default() });
+        assert_eq!(lenient.can_cast(&JihadiWarrior::default()), Ok(()));
+    }
+}
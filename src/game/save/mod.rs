@@ -0,0 +1,432 @@
+pub mod render;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chess::{Board, ChessMove, Color, Square, ALL_COLORS, ALL_SQUARES};
+use gloo::storage::{LocalStorage, Storage};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub use render::*;
+use super::board::UIBoard;
+use super::history::{Entry, Event};
+use super::{Cast, CastingRules, Game, Inventory, Kind, Spell};
+
+const STORAGE_KEY: &str = "magic-chess.save";
+
+/// Error Type for a save that can't be turned back into a game
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    Board(String),
+    Move(String),
+    Square(u8),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadError::Board(fen) => write!(f, "Invalid position {}", fen),
+            LoadError::Move(m) => write!(f, "Invalid move {}", m),
+            LoadError::Square(index) => write!(f, "Invalid square {}", index),
+        }
+    }
+}
+
+/// Everything written to storage, tagged with the format it was written in
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub enum Save {
+    /// spells stored by identifier and level only
+    #[serde(rename = "1")]
+    V1(Snapshot),
+    /// spells stored with their full state
+    #[serde(rename = "2")]
+    V2(Snapshot),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BoardRecord {
+    fen: String,
+    dims: (usize, usize),
+    /// occupied spell slots as (index into the spell grid, visible, spell)
+    spells: Vec<(usize, bool, Arc<dyn Spell>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CastRecord {
+    spell: Arc<dyn Spell>,
+    square: Option<u8>,
+    caster: u8,
+    shielded: Option<u8>,
+    expires_at: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum EventRecord {
+    Move { m: String, san: String },
+    Cast { caster: u8, spell: Arc<dyn Spell>, square: Option<u8>, hidden: bool },
+    Respond { by: u8, spell: Arc<dyn Spell> },
+    Effect { spell: Arc<dyn Spell>, square: Option<u8> },
+    Pickup { by: u8, spell: Arc<dyn Spell>, square: u8 },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EntryRecord {
+    ply: usize,
+    event: EventRecord,
+    board: BoardRecord,
+}
+
+/// A game in plain data, colors and squares by index and positions as FEN
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    board: BoardRecord,
+    moves: Vec<String>,
+    inventories: [Vec<Arc<dyn Spell>>; 2],
+    player: u8,
+    flipped: bool,
+    auto_rotate: bool,
+    casted_mine: Vec<((usize, usize), CastRecord)>,
+    casted_other: Vec<((usize, usize), CastRecord)>,
+    max_casts_per_turn: u8,
+    cooldown: u8,
+    cast_in_check: bool,
+    casts_this_turn: u8,
+    cooldowns: Vec<(u8, u32, usize)>,
+    stack: Vec<CastRecord>,
+    shielded: Option<u8>,
+    history: Vec<EntryRecord>,
+    /// the position the game started from, as FEN
+    origin: String,
+    hints: [u8; 2],
+    first_ply: usize,
+    halfmoves: usize,
+}
+
+fn color_index(color: Color) -> u8 {
+    color.to_index() as u8
+}
+
+fn color_at(index: u8) -> Color {
+    ALL_COLORS[index as usize % 2]
+}
+
+fn square_index(square: Square) -> u8 {
+    square.to_index() as u8
+}
+
+fn square_at(index: u8) -> Result<Square, LoadError> {
+    ALL_SQUARES.get(index as usize).copied().ok_or(LoadError::Square(index))
+}
+
+fn square_at_opt(index: Option<u8>) -> Result<Option<Square>, LoadError> {
+    index.map(square_at).transpose()
+}
+
+impl BoardRecord {
+    fn of(board: &UIBoard) -> Self {
+        let spells = board.spells().iter().enumerate().filter_map(|(index, kind)| match kind {
+            Kind::Transparent(spell) => Some((index, true, spell.clone())),
+            Kind::Opaque(spell) => Some((index, false, spell.clone())),
+            Kind::None => None,
+        }).collect();
+        Self { fen: board.board().to_string(), dims: board.dims(), spells }
+    }
+
+    fn board(&self) -> Result<UIBoard, LoadError> {
+        let board = Board::from_str(&self.fen).map_err(|_| LoadError::Board(self.fen.clone()))?;
+        let mut spells: Vec<Kind<dyn Spell>> = (0..self.dims.0 * self.dims.1).map(|_| Kind::None).collect();
+        for (index, visible, record) in &self.spells {
+            let spell = record.clone();
+            let slot = spells.get_mut(*index).ok_or(LoadError::Square(*index as u8))?;
+            *slot = match visible {
+                true => Kind::Transparent(spell),
+                false => Kind::Opaque(spell),
+            };
+        }
+        Ok(UIBoard::new(board, self.dims, Some(Arc::new(spells))))
+    }
+}
+
+impl CastRecord {
+    fn of(cast: &Cast) -> Self {
+        Self {
+            spell: cast.spell.clone(),
+            square: cast.square.map(square_index),
+            caster: color_index(cast.caster),
+            shielded: cast.shielded.map(color_index),
+            expires_at: cast.expires_at,
+        }
+    }
+
+    /// `armed` casts sit on the board as traps and get their spell re-armed on the square
+    fn cast(&self, armed: bool) -> Result<Cast, LoadError> {
+        let square = square_at_opt(self.square)?;
+        let mut spell = self.spell.clone();
+        if let (true, Some(square)) = (armed, square) {
+            spell = spell.arm(square).unwrap_or(spell);
+        }
+        Ok(Cast {
+            spell,
+            square,
+            caster: color_at(self.caster),
+            shielded: self.shielded.map(color_at),
+            expires_at: self.expires_at,
+        })
+    }
+}
+
+impl EventRecord {
+    fn of(event: &Event) -> Self {
+        match event {
+            Event::Move { m, san } => EventRecord::Move { m: m.to_string(), san: san.clone() },
+            Event::Cast { caster, spell, square, hidden } => EventRecord::Cast {
+                caster: color_index(*caster),
+                spell: spell.clone(),
+                square: square.map(square_index),
+                hidden: *hidden,
+            },
+            Event::Respond { by, spell } => EventRecord::Respond { by: color_index(*by), spell: spell.clone() },
+            Event::Effect { spell, square } => EventRecord::Effect { spell: spell.clone(), square: square.map(square_index) },
+            Event::Pickup { by, spell, square } => EventRecord::Pickup {
+                by: color_index(*by),
+                spell: spell.clone(),
+                square: square_index(*square),
+            },
+        }
+    }
+
+    fn event(&self) -> Result<Event, LoadError> {
+        Ok(match self {
+            EventRecord::Move { m, san } => Event::Move { m: parse_move(m)?, san: san.clone() },
+            EventRecord::Cast { caster, spell, square, hidden } => Event::Cast {
+                caster: color_at(*caster),
+                spell: spell.clone(),
+                square: square_at_opt(*square)?,
+                hidden: *hidden,
+            },
+            EventRecord::Respond { by, spell } => Event::Respond { by: color_at(*by), spell: spell.clone() },
+            EventRecord::Effect { spell, square } => Event::Effect { spell: spell.clone(), square: square_at_opt(*square)? },
+            EventRecord::Pickup { by, spell, square } => Event::Pickup {
+                by: color_at(*by),
+                spell: spell.clone(),
+                square: square_at(*square)?,
+            },
+        })
+    }
+}
+
+fn parse_move(m: &str) -> Result<ChessMove, LoadError> {
+    ChessMove::from_str(m).map_err(|_| LoadError::Move(m.to_owned()))
+}
+
+fn traps_of(casted: &HashMap<(usize, usize), Cast>) -> Vec<((usize, usize), CastRecord)> {
+    casted.iter().map(|(key, cast)| (*key, CastRecord::of(cast))).collect()
+}
+
+fn traps_from(records: &[((usize, usize), CastRecord)]) -> Result<HashMap<(usize, usize), Cast>, LoadError> {
+    records.iter().map(|(key, record)| Ok((*key, record.cast(true)?))).collect()
+}
+
+impl From<&Game> for Snapshot {
+    fn from(game: &Game) -> Self {
+        Self {
+            board: BoardRecord::of(&game.board),
+            moves: game.moves.iter().map(|m| m.to_string()).collect(),
+            inventories: game.inventories.clone().map(|inventory| inventory.spells()),
+            player: color_index(game.player),
+            flipped: game.flipped,
+            auto_rotate: game.auto_rotate,
+            casted_mine: traps_of(&game.casted_mine),
+            casted_other: traps_of(&game.casted_other),
+            max_casts_per_turn: game.rules.max_casts_per_turn,
+            cooldown: game.rules.cooldown,
+            cast_in_check: game.rules.cast_in_check,
+            casts_this_turn: game.casts_this_turn,
+            cooldowns: game.cooldowns.iter().map(|((color, id), ready)| (color_index(*color), *id, *ready)).collect(),
+            stack: game.stack.iter().map(CastRecord::of).collect(),
+            shielded: game.shielded.map(color_index),
+            history: game.history.iter().map(|entry| EntryRecord {
+                ply: entry.ply,
+                event: EventRecord::of(&entry.event),
+                board: BoardRecord::of(&entry.board),
+            }).collect(),
+            origin: game.origin.clone(),
+            hints: game.hints,
+            first_ply: game.first_ply,
+            halfmoves: game.halfmoves,
+        }
+    }
+}
+
+impl TryFrom<Snapshot> for Game {
+    type Error = LoadError;
+
+    fn try_from(snapshot: Snapshot) -> Result<Self, Self::Error> {
+        Ok(Self {
+            board: snapshot.board.board()?,
+            moves: snapshot.moves.iter().map(|m| parse_move(m)).collect::<Result<_, _>>()?,
+            inventories: snapshot.inventories.clone().map(Inventory::from_spells),
+            player: color_at(snapshot.player),
+            flipped: snapshot.flipped,
+            auto_rotate: snapshot.auto_rotate,
+            casted_mine: traps_from(&snapshot.casted_mine)?,
+            casted_other: traps_from(&snapshot.casted_other)?,
+            rules: CastingRules {
+                max_casts_per_turn: snapshot.max_casts_per_turn,
+                cooldown: snapshot.cooldown,
+                cast_in_check: snapshot.cast_in_check,
+            },
+            casts_this_turn: snapshot.casts_this_turn,
+            cooldowns: snapshot.cooldowns.iter().map(|(color, id, ready)| ((color_at(*color), *id), *ready)).collect(),
+            stack: snapshot.stack.iter().map(|record| record.cast(false)).collect::<Result<_, _>>()?,
+            shielded: snapshot.shielded.map(color_at),
+            history: snapshot.history.iter().map(|entry| Ok(Entry {
+                ply: entry.ply,
+                event: entry.event.event()?,
+                board: entry.board.board()?,
+            })).collect::<Result<_, LoadError>>()?,
+            origin: snapshot.origin,
+            hints: snapshot.hints,
+            first_ply: snapshot.first_ply,
+            halfmoves: snapshot.halfmoves,
+        })
+    }
+}
+
+impl Serialize for UIBoard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BoardRecord::of(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for UIBoard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BoardRecord::deserialize(deserializer)?.board().map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Snapshot::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Game::try_from(Snapshot::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+impl TryFrom<Save> for Game {
+    type Error = LoadError;
+
+    fn try_from(save: Save) -> Result<Self, Self::Error> {
+        match save {
+            // old spell records are upgraded as they are read, the rest of the format is unchanged
+            Save::V1(snapshot) | Save::V2(snapshot) => Game::try_from(snapshot),
+        }
+    }
+}
+
+/// the last saved game, if there is one that still loads
+pub fn load() -> Option<Game> {
+    let save = LocalStorage::get::<Save>(STORAGE_KEY).ok()?;
+    Game::try_from(save).map_err(|e| log::warn!("couldn't load the saved game: {}", e)).ok()
+}
+
+/// writes `game` over the last save
+pub fn store(game: &Game) {
+    if let Err(e) = LocalStorage::set(STORAGE_KEY, Save::V2(Snapshot::from(game))) {
+        log::warn!("couldn't save the game: {}", e);
+    }
+}
+
+/// forgets the last save
+pub fn clear() {
+    LocalStorage::delete(STORAGE_KEY);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::game::testing::{midgame, square};
+    use crate::game::JihadiWarrior;
+
+    fn round_trip(game: &Game) -> Game {
+        serde_json::from_str(&serde_json::to_string(game).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn new_game_round_trips() {
+        let game = Game::new();
+        assert_eq!(round_trip(&game), game);
+    }
+
+    #[test]
+    fn game_in_progress_round_trips() {
+        let game = midgame();
+        assert!(game.pending().is_some());
+        assert!(!game.traps(Color::Black).is_empty());
+        assert_eq!(round_trip(&game), game);
+    }
+
+    #[test]
+    fn board_round_trips() {
+        let board = midgame().board().clone();
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(serde_json::from_str::<UIBoard>(&json).unwrap(), board);
+    }
+
+    #[test]
+    fn spell_state_round_trips() {
+        let spell: Arc<dyn Spell> = JihadiWarrior::with_level(2).arm(square("h3")).unwrap();
+        let json = serde_json::to_string(&spell).unwrap();
+        let back: Arc<dyn Spell> = serde_json::from_str(&json).unwrap();
+        assert!(*back == *spell);
+        assert!(*back != *JihadiWarrior::with_level(2).arm(square("h4")).unwrap());
+    }
+
+    #[test]
+    fn unknown_spells_are_rejected() {
+        assert!(serde_json::from_value::<Arc<dyn Spell>>(json!({ "id": 99 })).is_err());
+    }
+
+    /// rewrites every spell record the way version 1 wrote them, identifier and level only
+    fn downgrade(value: &mut Value) {
+        match value {
+            Value::Object(map) if map.contains_key("id") => {
+                let level = map.get("state").and_then(|state| state.get("level")).cloned().unwrap_or(json!(1));
+                map.remove("state");
+                map.insert("level".to_owned(), level);
+            },
+            Value::Object(map) => map.values_mut().for_each(downgrade),
+            Value::Array(items) => items.iter_mut().for_each(downgrade),
+            _ => {},
+        }
+    }
+
+    #[test]
+    fn version_1_saves_still_load() {
+        let game = midgame();
+        let mut save = serde_json::to_value(Save::V2(Snapshot::from(&game))).unwrap();
+        downgrade(&mut save);
+        save["version"] = json!("1");
+        let save: Save = serde_json::from_value(save).unwrap();
+        assert_eq!(Game::try_from(save).unwrap(), game);
+    }
+
+    #[test]
+    fn incomplete_saves_dont_load() {
+        let save = serde_json::to_value(Save::V2(Snapshot::from(&midgame()))).unwrap();
+        for field in ["origin", "hints", "first_ply", "halfmoves"] {
+            let mut partial = save.clone();
+            partial.as_object_mut().unwrap().remove(field);
+            assert!(serde_json::from_value::<Save>(partial).is_err(), "{}", field);
+        }
+    }
+}
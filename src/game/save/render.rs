@@ -0,0 +1,43 @@
+use yew::prelude::*;
+
+use super::clear;
+use crate::game::draft::Draft;
+use crate::game::history::Event;
+use crate::game::Game;
+
+/// offers to pick the saved game back up instead of drafting a new one
+pub fn render(saved: &UseStateHandle<Option<Game>>, game: &UseStateHandle<Game>, draft: &UseStateHandle<Option<Draft>>) -> Html {
+    let last = match saved.as_ref() {
+        Some(last) => last.clone(),
+        None => return html! {},
+    };
+    let moves = last.history().iter().filter(|entry| matches!(entry.event, Event::Move { .. })).count();
+    let summary = format!("{} move(s) played, {:?} to act", moves, last.acting_side());
+    let resume = {
+        let saved = saved.clone();
+        let game = game.clone();
+        let draft = draft.clone();
+        Callback::from(move |_| {
+            game.set(last.clone());
+            draft.set(None);
+            saved.set(None);
+        })
+    };
+    let fresh = {
+        let saved = saved.clone();
+        Callback::from(move |_| {
+            clear();
+            saved.set(None);
+        })
+    };
+    html! {
+        <div class="resume p-6 shadow-2xl rounded-xl">
+            <span class="text-base">{"Resume last game?"}</span>
+            <span class="text-sm">{summary}</span>
+            <div class="resume--actions">
+                <button class="menu--pass rounded-lg" onclick={resume}>{"Resume"}</button>
+                <button class="menu--pass rounded-lg" onclick={fresh}>{"New game"}</button>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,415 @@
+//! Links that open the app in a given position or replay a whole game. The state is packed into
+//! bytes and put in the URL fragment as base64url, e.g. `https://…/#g=AQEA…`.
+//!
+//! Layout: a version byte, a kind byte (position or game, with the hints taken in the high nibble:
+//! two bits for white, two for black) and a position. A game's position is
+//! where it started, followed by one byte per move (its index among the legal moves) and
+//! three or two byte records for casts and responses. Spells are one byte: id in the high
+//! nibble, level in the low one. Only links of the current version open.
+use std::sync::Arc;
+
+use chess::{Board, BoardBuilder, CastleRights, Color, File, MoveGen, Square, ALL_COLORS, ALL_PIECES, ALL_SQUARES};
+
+use super::ai::SpellId;
+use super::board::UIBoard;
+use super::history::{Event, Recorded};
+use super::{restore, Cast, Game, Inventory, Kind, Spell};
+use crate::window;
+
+const VERSION: u8 = 4;
+const FRAGMENT: &str = "#g=";
+
+const POSITION: u8 = 0;
+const GAME: u8 = 1;
+
+/// action markers in a game, anything below is the index of a legal move
+const CAST: u8 = 0xFC;
+const RESPOND: u8 = 0xFD;
+
+/// Error Type for a link that doesn't decode to a game
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShareError {
+    Encoding,
+    Version(u8),
+    Truncated,
+    Position,
+    Spell(u8),
+    Action(usize),
+    /// a history entry that doesn't replay from the one before, by index
+    History(usize),
+}
+
+impl std::fmt::Display for ShareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShareError::Encoding => write!(f, "Not a game link"),
+            ShareError::Version(version) => write!(f, "Unsupported link version {}", version),
+            ShareError::Truncated => write!(f, "The link is cut short"),
+            ShareError::Position => write!(f, "The link holds an impossible position"),
+            ShareError::Spell(byte) => write!(f, "Unknown spell {:#04x}", byte),
+            ShareError::Action(index) => write!(f, "Action {} can't be replayed", index),
+            ShareError::History(index) => write!(f, "History entry {} can't be shared", index),
+        }
+    }
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// base64url without padding
+fn encode_base64(bytes: &[u8]) -> String {
+    bytes.chunks(3).flat_map(|chunk| {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        (0..=chunk.len()).map(move |i| ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char)
+    }).collect()
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, ShareError> {
+    let values = text.bytes()
+        .map(|c| ALPHABET.iter().position(|a| *a == c).map(|v| v as u32).ok_or(ShareError::Encoding))
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.len() % 4 == 1 {
+        return Err(ShareError::Encoding);
+    }
+    Ok(values.chunks(4).flat_map(|chunk| {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, v)| n | v << (18 - 6 * i));
+        (0..chunk.len() - 1).map(move |i| (n >> (16 - 8 * i)) as u8)
+    }).collect())
+}
+
+fn spell_byte(spell: &dyn Spell) -> u8 {
+    (spell.identifier() as u8) << 4 | spell.level().min(15)
+}
+
+/// Reads the bytes of a link front to back
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, ShareError> {
+        let byte = *self.bytes.get(self.at).ok_or(ShareError::Truncated)?;
+        self.at += 1;
+        Ok(byte)
+    }
+
+    fn done(&self) -> bool {
+        self.at >= self.bytes.len()
+    }
+
+    fn spell(&mut self) -> Result<Arc<dyn Spell>, ShareError> {
+        let byte = self.byte()?;
+        restore((byte >> 4) as u32, byte & 15).ok_or(ShareError::Spell(byte))
+    }
+
+    fn square(&mut self) -> Result<Square, ShareError> {
+        Ok(ALL_SQUARES[(self.byte()? & 63) as usize])
+    }
+
+    fn color(byte: u8) -> Color {
+        ALL_COLORS[(byte >> 7) as usize]
+    }
+}
+
+fn write_position(game: &Game, out: &mut Vec<u8>) {
+    let board = game.raw_board();
+    let ply = game.moves.len();
+    out.extend(board.combined().0.to_be_bytes());
+    let pieces = board.combined().map(|square| {
+        let piece = board.piece_on(square).map(|p| p.to_index()).unwrap_or_default() as u8;
+        let color = board.color_on(square).map(|c| c.to_index()).unwrap_or_default() as u8;
+        color << 3 | piece
+    }).collect::<Vec<_>>();
+    out.extend(pieces.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or_default()));
+    let castling = board.castle_rights(Color::White).to_index() | board.castle_rights(Color::Black).to_index() << 2;
+    out.push((board.side_to_move().to_index() | castling << 1) as u8);
+    out.push(board.en_passant().map(|square| square.get_file().to_index() as u8).unwrap_or(0xFF));
+    let fullmove = ((game.first_ply + ply) / 2 + 1).min(u16::MAX as usize) as u16;
+    out.extend(fullmove.to_be_bytes());
+    out.push(game.halfmoves.min(255) as u8);
+
+    let spells = game.board.spells().iter().enumerate().filter_map(|(index, kind)| {
+        let square = (index % 8) * 8 + index / 8;
+        match kind {
+            Kind::Transparent(spell) => Some([1 << 6 | square as u8, spell_byte(spell.as_ref())]),
+            Kind::Opaque(spell) => Some([square as u8, spell_byte(spell.as_ref())]),
+            Kind::None => None,
+        }
+    }).collect::<Vec<_>>();
+    out.push(spells.len() as u8);
+    out.extend(spells.concat());
+
+    for inventory in &game.inventories {
+        out.push(inventory.spells().len() as u8);
+        out.extend(inventory.spells().iter().map(|spell| spell_byte(spell.as_ref())));
+    }
+
+    let traps = ALL_COLORS.iter().flat_map(|color| game.casted(*color).values().filter_map(move |cast| {
+        let square = cast.square?.to_index() as u8;
+        let expiry = cast.expires_at.map(|at| at.saturating_sub(ply).min(254) as u8).unwrap_or(0xFF);
+        let shielded = cast.shielded.map(|side| side.to_index() as u8 + 1).unwrap_or_default();
+        Some([(color.to_index() as u8) << 7 | square, spell_byte(cast.spell.as_ref()), expiry, shielded])
+    })).collect::<Vec<_>>();
+    out.push(traps.len() as u8);
+    out.extend(traps.concat());
+
+    out.push(game.stack.len() as u8);
+    for cast in &game.stack {
+        let target = cast.square.map(|square| 1 << 6 | square.to_index() as u8).unwrap_or_default();
+        out.extend([(cast.caster.to_index() as u8) << 7 | target, spell_byte(cast.spell.as_ref())]);
+    }
+    out.push(game.casts_this_turn);
+
+    let cooldowns = game.cooldowns.iter()
+        .filter(|(_, ready)| **ready > ply)
+        .map(|((color, id), ready)| [(color.to_index() as u8) << 7 | *id as u8, (ready - ply).min(255) as u8])
+        .collect::<Vec<_>>();
+    out.push(cooldowns.len() as u8);
+    out.extend(cooldowns.concat());
+}
+
+fn read_position(reader: &mut Reader) -> Result<Game, ShareError> {
+    let mut occupied = 0u64;
+    for _ in 0..8 {
+        occupied = occupied << 8 | reader.byte()? as u64;
+    }
+    let squares = ALL_SQUARES.iter().filter(|square| occupied >> square.to_index() & 1 == 1).copied().collect::<Vec<_>>();
+    let mut nibbles = Vec::new();
+    for _ in 0..squares.len().div_ceil(2) {
+        let byte = reader.byte()?;
+        nibbles.extend([byte >> 4, byte & 15]);
+    }
+    let mut builder = BoardBuilder::new();
+    for (square, nibble) in squares.iter().zip(nibbles) {
+        let piece = *ALL_PIECES.get((nibble & 7) as usize).ok_or(ShareError::Position)?;
+        builder.piece(*square, piece, ALL_COLORS[(nibble >> 3) as usize]);
+    }
+    let flags = reader.byte()? as usize;
+    builder.side_to_move(ALL_COLORS[flags & 1]);
+    builder.castle_rights(Color::White, CastleRights::from_index(flags >> 1 & 3));
+    builder.castle_rights(Color::Black, CastleRights::from_index(flags >> 3 & 3));
+    let en_passant = reader.byte()?;
+    builder.en_passant((en_passant < 8).then(|| File::from_index(en_passant as usize)));
+    let board = Board::try_from(&builder).map_err(|_| ShareError::Position)?;
+    let fullmove = u16::from_be_bytes([reader.byte()?, reader.byte()?]).max(1) as usize;
+    let halfmoves = reader.byte()? as usize;
+
+    let mut spells: Vec<Kind<dyn Spell>> = (0..64).map(|_| Kind::None).collect();
+    for _ in 0..reader.byte()? {
+        let slot = reader.byte()?;
+        let square = ALL_SQUARES[(slot & 63) as usize];
+        let spell = reader.spell()?;
+        spells[square.get_file().to_index() * 8 + square.get_rank().to_index()] = match slot >> 6 & 1 {
+            1 => Kind::Transparent(spell),
+            _ => Kind::Opaque(spell),
+        };
+    }
+
+    let mut inventories: [Inventory; 2] = Default::default();
+    for inventory in inventories.iter_mut() {
+        let held = (0..reader.byte()?).map(|_| reader.spell()).collect::<Result<_, _>>()?;
+        *inventory = Inventory::from_spells(held);
+    }
+
+    let mut game = Game {
+        first_ply: 2 * (fullmove - 1) + board.side_to_move().to_index(),
+        halfmoves,
+        board: UIBoard::new(board, (8, 8), Some(Arc::new(spells))),
+        inventories,
+        ..Game::default()
+    };
+
+    for _ in 0..reader.byte()? {
+        let slot = reader.byte()?;
+        let owner = Reader::color(slot);
+        let square = ALL_SQUARES[(slot & 63) as usize];
+        let spell = reader.spell()?;
+        let expiry = reader.byte()?;
+        let shielded = match reader.byte()? {
+            0 => None,
+            side => Some(ALL_COLORS[(side as usize - 1) & 1]),
+        };
+        let armed = spell.arm(square).ok_or(ShareError::Position)?;
+        game.casted_mut(owner).insert((square.get_rank().to_index(), square.get_file().to_index()), Cast {
+            expires_at: (expiry != 0xFF).then_some(expiry as usize),
+            shielded,
+            ..Cast::new(armed, Some(square), owner)
+        });
+    }
+    for _ in 0..reader.byte()? {
+        let slot = reader.byte()?;
+        let square = (slot >> 6 & 1 == 1).then(|| ALL_SQUARES[(slot & 63) as usize]);
+        let spell = reader.spell()?;
+        game.stack.push(Cast::new(spell, square, Reader::color(slot)));
+    }
+    game.casts_this_turn = reader.byte()?;
+    for _ in 0..reader.byte()? {
+        let slot = reader.byte()?;
+        let ready = reader.byte()? as usize;
+        game.cooldowns.insert((Reader::color(slot), (slot & 127) as u32), ready);
+    }
+    game.origin = game.to_fen();
+    Ok(game)
+}
+
+/// hints taken by the end of the game, for the high nibble of the kind byte
+fn hints_taken(game: &Game) -> u8 {
+    game.hints[0].min(3) << 4 | game.hints[1].min(3) << 6
+}
+
+/// the spell packed into `byte` as the acting side's actions name it
+fn spell_id(byte: u8) -> SpellId {
+    SpellId { id: (byte >> 4) as u32, level: byte & 15 }
+}
+
+fn replay(game: Game, reader: &mut Reader) -> Result<Game, ShareError> {
+    let mut records = Vec::new();
+    while !reader.done() {
+        records.push(match reader.byte()? {
+            CAST => Recorded::Cast(spell_id(reader.byte()?), reader.square()?),
+            RESPOND => Recorded::Respond(spell_id(reader.byte()?)),
+            index => Recorded::Move(index),
+        });
+    }
+    let mut steps = game.replay(records, |board, index| MoveGen::new_legal(board).nth(*index as usize))
+        .map_err(ShareError::Action)?;
+    Ok(steps.pop().map_or(game, |step| step.game))
+}
+
+impl Game {
+    /// the position alone, packed for a link
+    pub fn share_position(&self) -> String {
+        let mut out = vec![VERSION, POSITION | hints_taken(self)];
+        write_position(self, &mut out);
+        encode_base64(&out)
+    }
+
+    /// the game from its start, packed for a link
+    pub fn share_game(&self) -> Result<String, ShareError> {
+        let start = self.start().map_err(|_| ShareError::Position)?;
+        let mut out = vec![VERSION, GAME | hints_taken(self)];
+        write_position(&start, &mut out);
+        let mut before = start.raw_board().to_owned();
+        for (i, entry) in self.history.iter().enumerate() {
+            match &entry.event {
+                Event::Move { m, .. } => {
+                    let index = MoveGen::new_legal(&before).position(|legal| legal == *m).ok_or(ShareError::History(i))?;
+                    out.push(index as u8);
+                },
+                Event::Cast { spell, square: Some(square), .. } => out.extend([CAST, spell_byte(spell.as_ref()), square.to_index() as u8]),
+                Event::Respond { spell, .. } => out.extend([RESPOND, spell_byte(spell.as_ref())]),
+                _ => {},
+            }
+            before = entry.board.board().to_owned();
+        }
+        Ok(encode_base64(&out))
+    }
+
+    /// the game or position packed into `code` by `share_game` or `share_position`
+    pub fn from_share(code: &str) -> Result<Self, ShareError> {
+        let bytes = decode_base64(code)?;
+        let mut reader = Reader { bytes: &bytes, at: 0 };
+        match reader.byte()? {
+            VERSION => {},
+            version => return Err(ShareError::Version(version)),
+        }
+        let kind = reader.byte()?;
+        let game = read_position(&mut reader)?;
+        let mut game = match kind & 15 {
+            GAME => replay(game, &mut reader)?,
+            _ => game,
+        };
+        game.hints = [kind >> 4 & 3, kind >> 6];
+        Ok(game)
+    }
+}
+
+/// a link to this page opening `code`
+pub fn link(code: &str) -> String {
+    let location = window().location();
+    let page = format!("{}{}", location.origin().unwrap_or_default(), location.pathname().unwrap_or_default());
+    format!("{}{}{}", page, FRAGMENT, code)
+}
+
+/// the game linked in the page's fragment, which is cleared so a reload resumes normally
+pub fn from_location() -> Option<Game> {
+    let location = window().location();
+    let hash = location.hash().ok()?;
+    let code = hash.strip_prefix(FRAGMENT)?;
+    let game = Game::from_share(code).map_err(|e| log::warn!("couldn't open the link: {}", e)).ok();
+    let _ = location.set_hash("");
+    game
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{midgame, square};
+
+    #[test]
+    fn base64_round_trips() {
+        for bytes in [&b""[..], b"a", b"ab", b"abc", b"\xff\x00\x10\x80"] {
+            assert_eq!(decode_base64(&encode_base64(bytes)).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        let game = Game::from_fen(&midgame().to_fen()).unwrap();
+        assert_eq!(Game::from_share(&game.share_position()).unwrap().to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn shielded_traps_are_kept() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1 - -/- wW@e5~6#b,bW@d4 0 -";
+        let game = Game::from_fen(fen).unwrap();
+        assert_eq!(Game::from_share(&game.share_position()).unwrap().to_fen(), fen);
+    }
+
+    #[test]
+    fn games_replay() {
+        let game = midgame();
+        let shared = Game::from_share(&game.share_game().unwrap()).unwrap();
+        assert_eq!(shared.to_pgn(), game.to_pgn());
+        assert_eq!(shared.to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn casts_replay_the_level_cast() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 - W3W/- - 0 -").unwrap();
+        let weak = game.spells()[1].clone();
+        let game = game.cast_spell(weak, square("e5")).unwrap();
+        let shared = Game::from_share(&game.share_game().unwrap()).unwrap();
+        assert_eq!(shared.inventory(Color::White).spells()[0].level(), 3);
+    }
+
+    #[test]
+    fn games_that_dont_replay_arent_shared() {
+        let mut game = midgame();
+        game.history[0].event = Event::Move { m: "e2e5".parse().unwrap(), san: "e5".to_owned() };
+        assert_eq!(game.share_game(), Err(ShareError::History(0)));
+    }
+
+    #[test]
+    fn hints_taken_are_kept() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 - -/- - 0 - 0/2").unwrap();
+        let game = game.make_move_new("e1d1".parse().unwrap());
+        assert_eq!(Game::from_share(&game.share_game().unwrap()).unwrap().hints_left(Color::Black), 1);
+        assert_eq!(Game::from_share(&game.share_position()).unwrap().hints_left(Color::Black), 1);
+    }
+
+    #[test]
+    fn links_stay_short() {
+        assert!(midgame().share_game().unwrap().len() < 80);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert_eq!(Game::from_share(&encode_base64(&[9, 0])).unwrap_err(), ShareError::Version(9));
+        for old in 1..VERSION {
+            let mut bytes = decode_base64(&midgame().share_position()).unwrap();
+            bytes[0] = old;
+            assert_eq!(Game::from_share(&encode_base64(&bytes)).unwrap_err(), ShareError::Version(old));
+        }
+    }
+}
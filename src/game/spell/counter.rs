@@ -0,0 +1,74 @@
+use chess::{Color, Square};
+
+use super::Spell;
+use crate::game::{Cast, Game, Speed};
+
+/// An instant spell that only ever answers another one: everything but how it changes the cast
+/// it responds to is the same for all of them
+macro_rules! counter {
+    ($(#[$doc:meta])* $spell:ident, $id:literal, $name:literal, $class:literal, $description:literal, |$cast:pat_param, $by:pat_param| $respond:expr) => {
+        $(#[$doc])*
+        #[derive(Clone, PartialEq, Debug, Default)]
+        pub struct $spell;
+
+        impl Spell for $spell {
+            fn class_list(&self) -> String {
+                concat!("spell spell--", $class).to_owned()
+            }
+
+            fn execute(&self, game: Game, _square: Option<Square>) -> Game {
+                game
+            }
+
+            fn update(&mut self, _m: &chess::ChessMove) {}
+
+            fn name(&self) -> &'static str {
+                $name
+            }
+
+            fn description(&self) -> &'static str {
+                $description
+            }
+
+            fn identifier(&self) -> u32 {
+                $id
+            }
+
+            fn speed(&self) -> Speed {
+                Speed::Instant
+            }
+
+            fn respond(&self, $cast: Cast, $by: Color) -> Option<Cast> {
+                $respond
+            }
+        }
+    };
+}
+
+counter!(
+    /// Protects the reacting side's pieces from the spell it responds to
+    Shield, 1, "Shield", "shield",
+    "Your pieces are unaffected by the spell your opponent just cast",
+    |cast, by| Some(Cast {
+        shielded: Some(by),
+        ..cast
+    })
+);
+
+counter!(
+    /// Turns the spell it responds to back towards its caster
+    Reflect, 2, "Reflect", "reflect",
+    "Mirror the spell your opponent just cast onto their own half of the board",
+    |cast, by| Some(Cast {
+        square: cast.square.map(|s| Square::make_square(chess::Rank::from_index(7 - s.get_rank().to_index()), s.get_file())),
+        caster: by,
+        ..cast
+    })
+);
+
+counter!(
+    /// Cancels the spell it responds to
+    Dispel, 3, "Dispel", "dispel",
+    "Cancel the spell your opponent just cast",
+    |_cast, _by| None
+);
@@ -4,31 +4,97 @@ use std::fmt::Debug;
 
 pub use render::*;
 mod warrior;
+mod counter;
+mod registry;
 pub use warrior::*;
-use super::Game;
+pub use counter::*;
+pub use registry::{from_spell_token, from_symbol, restore, spell_token, symbol};
+use super::{Cast, Game, Speed};
 
-pub trait Spell: Debug {
+pub trait Spell: Debug + Send + Sync {
     fn class_list(&self) -> String;
     fn execute(&self, game: Game, square: Option<chess::Square>) -> Game;
+    #[allow(dead_code)]
     fn update(&mut self, m: &chess::ChessMove);
     fn name(&self) -> &'static str;
     fn description(&self) -> &'static str;
     fn identifier(&self) -> u32;
+    fn level(&self) -> u8 {
+        1
+    }
+    /// how many of the per-turn casts this spell uses up
+    fn cost(&self) -> u8 {
+        1
+    }
+    /// how many of its caster's turns the spell lasts once cast, `None` if it is over at once
+    fn duration(&self) -> Option<u8> {
+        None
+    }
+    /// (file, rank) offsets from the target square that the spell affects
+    fn area(&self) -> Vec<(i32, i32)> {
+        Vec::new()
+    }
+    /// combines a duplicate of this spell into a stronger one, `None` if it doesn't level
+    fn merge(&self, _other: &dyn Spell) -> Option<Arc<dyn Spell>> {
+        None
+    }
+    /// a copy of this spell lying in wait on `square`, `None` if it takes effect right away
+    fn arm(&self, _square: chess::Square) -> Option<Arc<dyn Spell>> {
+        None
+    }
+    /// css class of the effect shown where the spell goes off
+    fn effect_class(&self) -> Option<&'static str> {
+        None
+    }
+    fn speed(&self) -> Speed {
+        Speed::Sorcery
+    }
+    /// how this spell changes a cast it responds to, `None` cancels it
+    fn respond(&self, cast: Cast, _by: chess::Color) -> Option<Cast> {
+        Some(cast)
+    }
+    /// rough worth in centipawns, for the computer opponent weighing spells against pieces
+    fn worth(&self) -> i32 {
+        100
+    }
+    /// whatever about this copy changes during play, `null` for spells that never change
+    fn state(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+}
+
+/// one fresh, level 1 copy of every spell in the game
+pub fn catalogue() -> Vec<Arc<dyn Spell>> {
+    vec![
+        Arc::new(JihadiWarrior::default()),
+        Arc::new(Shield),
+        Arc::new(Reflect),
+        Arc::new(Dispel),
+    ]
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub enum Kind<T: Spell + ?Sized> {
     Transparent(Arc<T>),
     Opaque(Arc<T>),
     None,
 }
 
-impl<T: Spell + PartialEq> PartialEq for Kind<T> {
+impl<T: Spell + ?Sized> Clone for Kind<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Kind::Transparent(s) => Kind::Transparent(s.clone()),
+            Kind::Opaque(s) => Kind::Opaque(s.clone()),
+            Kind::None => Kind::None,
+        }
+    }
+}
+
+impl<T: Spell + PartialEq + ?Sized> PartialEq for Kind<T> {
     fn eq(&self, other: &Self) -> bool {
-        // TODO: incomplete
         match (self, other) {
-            (Kind::Transparent(_), Kind::Transparent(_)) => true,
-            (Kind::Opaque(_), Kind::Opaque(_)) => true,
+            (Kind::Transparent(a), Kind::Transparent(b)) | (Kind::Opaque(a), Kind::Opaque(b)) => a == b,
+            (Kind::None, Kind::None) => true,
             _ => false,
         }
     }
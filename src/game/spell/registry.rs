@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use super::{catalogue, Dispel, JihadiWarrior, Reflect, Shield, Spell};
+
+/// rebuilds a spell from its identifier and level, `None` if either is unknown
+pub fn restore(id: u32, level: u8) -> Option<Arc<dyn Spell>> {
+    let base = catalogue().into_iter().find(|spell| spell.identifier() == id)?;
+    let mut spell = base.clone();
+    // levels are only ever gained by merging, so merging fresh copies retraces them
+    while spell.level() < level {
+        spell = spell.merge(base.as_ref())?;
+    }
+    Some(spell)
+}
+
+/// one-letter names of the spells by identifier, as used in Magic FEN
+const SYMBOLS: [char; 4] = ['W', 'S', 'R', 'D'];
+
+/// the letter standing for `spell` in Magic FEN
+pub fn symbol(spell: &dyn Spell) -> char {
+    SYMBOLS.get(spell.identifier() as usize).copied().unwrap_or('?')
+}
+
+/// a fresh spell at `level` from its Magic FEN letter, in either case
+pub fn from_symbol(symbol: char, level: u8) -> Option<Arc<dyn Spell>> {
+    let id = SYMBOLS.iter().position(|s| *s == symbol.to_ascii_uppercase())?;
+    restore(id as u32, level)
+}
+
+/// the letter of `spell` followed by its level when that isn't 1, e.g. `W2`
+pub fn spell_token(spell: &dyn Spell) -> String {
+    match spell.level() {
+        1 => symbol(spell).to_string(),
+        level => format!("{}{}", symbol(spell), level),
+    }
+}
+
+/// a fresh spell from a letter in either case with an optional level, as `spell_token` writes it
+pub fn from_spell_token(token: &str) -> Option<Arc<dyn Spell>> {
+    let letter = token.chars().next()?;
+    let level = match &token[letter.len_utf8()..] {
+        "" => 1,
+        digits => digits.parse().ok()?,
+    };
+    from_symbol(letter, level)
+}
+
+/// rebuilds a spell from its identifier and the state it reported, `None` if either doesn't fit
+fn revive(id: u32, state: Value) -> Option<Box<dyn Spell>> {
+    match id {
+        0 => JihadiWarrior::from_state(state).map(|spell| Box::new(spell) as Box<dyn Spell>),
+        // the counterspells carry no state of their own
+        1 => Some(Box::new(Shield)),
+        2 => Some(Box::new(Reflect)),
+        3 => Some(Box::new(Dispel)),
+        _ => None,
+    }
+}
+
+/// A spell as it is serialized: its identifier plus its state
+#[derive(Serialize, Deserialize)]
+struct Record {
+    id: u32,
+    /// only written by version 1 saves, later ones keep the level in `state`
+    #[serde(default, skip_serializing)]
+    level: Option<u8>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    state: Value,
+}
+
+impl PartialEq for dyn Spell {
+    fn eq(&self, other: &Self) -> bool {
+        self.identifier() == other.identifier() && self.state() == other.state()
+    }
+}
+
+impl Serialize for dyn Spell {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Record { id: self.identifier(), level: None, state: self.state() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn Spell> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = Record::deserialize(deserializer)?;
+        let state = match (record.state, record.level) {
+            (Value::Null, Some(level)) => restore(record.id, level).map(|spell| spell.state()).unwrap_or_default(),
+            (state, _) => state,
+        };
+        revive(record.id, state).ok_or_else(|| D::Error::custom(format!("unknown spell {}", record.id)))
+    }
+}
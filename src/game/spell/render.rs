@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use yew::prelude::*;
 use super::Kind;
 use crate::game::spell::Spell;
@@ -22,4 +24,91 @@ pub fn render<T: Spell + ?Sized>(spell: &Kind<T>) -> Html {
         },
 
     }
-}
\ No newline at end of file
+}
+/// A spell shown in the detail card
+#[derive(Clone, Debug)]
+pub struct Detail {
+    pub spell: Arc<dyn Spell>,
+    /// turns left for a spell already armed on the board
+    pub remaining: Option<usize>,
+}
+
+/// shows the detail card of `spell` while the mouse is over the element
+pub fn hover_detail(detail: &UseStateHandle<Option<Detail>>, spell: Arc<dyn Spell>, remaining: Option<usize>) -> (Callback<MouseEvent>, Callback<MouseEvent>) {
+    let show = {
+        let detail = detail.clone();
+        Callback::from(move |_| detail.set(Some(Detail { spell: spell.clone(), remaining })))
+    };
+    let hide = {
+        let detail = detail.clone();
+        Callback::from(move |_| detail.set(None))
+    };
+    (show, hide)
+}
+
+/// long press opens the context menu on touch screens, which opens the detail card instead
+pub fn press_detail(detail: &UseStateHandle<Option<Detail>>, spell: Arc<dyn Spell>, remaining: Option<usize>) -> Callback<MouseEvent> {
+    let detail = detail.clone();
+    Callback::from(move |e: MouseEvent| {
+        e.prevent_default();
+        detail.set(Some(Detail { spell: spell.clone(), remaining }))
+    })
+}
+
+/// grid of the squares around the target a spell affects
+fn render_area(spell: &dyn Spell) -> Html {
+    let area = spell.area();
+    if area.is_empty() {
+        return html! {};
+    }
+    let reach = area.iter().map(|(f, r)| f.abs().max(r.abs())).max().unwrap_or(0) + 1;
+    html! {
+        <div class="detail--area" style={format!("grid-template-columns: repeat({}, 1fr);", 2 * reach + 1)}>
+            {(-reach..=reach).rev().flat_map(|rank| (-reach..=reach).map(move |file| (file, rank))).map(|(file, rank)| {
+                let classes = classes!(
+                    "detail--cell",
+                    area.contains(&(file, rank)).then_some("detail--cell--hit"),
+                    (file == 0 && rank == 0).then_some("detail--cell--target"),
+                );
+                html! { <div class={classes}></div> }
+            }).collect::<Html>()}
+        </div>
+    }
+}
+
+pub fn render_detail(detail: &UseStateHandle<Option<Detail>>) -> Html {
+    let current = match detail.as_ref() {
+        Some(current) => current,
+        None => return html! {},
+    };
+    let spell = current.spell.as_ref();
+    let duration = match (current.remaining, spell.duration()) {
+        (Some(remaining), _) => format!("{} more turn(s)", remaining),
+        (None, Some(turns)) => format!("{} turn(s)", turns),
+        (None, None) => "instant".to_owned(),
+    };
+    let close = {
+        let detail = detail.clone();
+        Callback::from(move |_| detail.set(None))
+    };
+    html! {
+        <div class="detail p-6 shadow-2xl rounded-xl" onclick={close}>
+            <div class="detail--header">
+                <div class="spell--icon">
+                    <div class={spell.class_list()}>
+                    </div>
+                </div>
+                <span class="text-base">{spell.name()}</span>
+                <span class="spell--level rounded-lg">{format!("Lv {}", spell.level())}</span>
+            </div>
+            <p class="text-sm">{spell.description()}</p>
+            <dl class="detail--stats text-sm">
+                <dt>{"Duration"}</dt>
+                <dd>{duration}</dd>
+                <dt>{"Cost"}</dt>
+                <dd>{format!("{} cast(s)", spell.cost())}</dd>
+            </dl>
+            {render_area(spell)}
+        </div>
+    }
+}
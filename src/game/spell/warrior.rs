@@ -1,10 +1,24 @@
 use std::sync::Arc;
 
-use lazy_static::__Deref;
 use log::info;
 
+use serde::{Deserialize, Serialize};
+
 use super::Spell;
-use crate::game::{board::UIBoard, Game};
+use crate::game::board::{parse_square, square_name};
+use crate::game::Game;
+
+/// highest level a Jihadi Warrior can be merged up to
+const MAX_LEVEL: u8 = 3;
+
+/// What changes about a warrior during play
+#[derive(Serialize, Deserialize)]
+struct State {
+    level: u8,
+    on: Option<String>,
+    valid_for: i32,
+    valid: bool,
+}
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct JihadiWarrior {
@@ -22,7 +36,7 @@ impl Default for JihadiWarrior {
         Self {
             id: 0,
             name: "Jihadi Warrior",
-            description: "Remove all pieces in a 3x3 square around the Jihadi Warrior",
+            description: "Remove all pieces in a 3x3 square around the Jihadi Warrior, growing by one ring per level. Cast on an empty square it hides there instead and goes off once an enemy piece steps on or past it",
             level: 1,
             on: None, //chess::Square::make_square(chess::Rank::Fourth, chess::File::E),
             valid_for: 3,
@@ -31,41 +45,63 @@ impl Default for JihadiWarrior {
     }
 }
 
+impl JihadiWarrior {
+    /// a warrior at `level`, with radius and duration scaled accordingly
+    pub fn with_level(level: u8) -> Self {
+        let level = level.clamp(1, MAX_LEVEL);
+        Self {
+            level,
+            valid_for: 2 + level as i32,
+            ..Self::default()
+        }
+    }
+
+    /// a warrior in the state `Spell::state` reported, `None` if it doesn't describe one
+    pub fn from_state(state: serde_json::Value) -> Option<Self> {
+        let state: State = serde_json::from_value(state).ok()?;
+        let on = match state.on {
+            Some(name) => Some(parse_square(&name)?),
+            None => None,
+        };
+        Some(Self {
+            level: state.level.clamp(1, MAX_LEVEL),
+            on,
+            valid_for: state.valid_for,
+            valid: state.valid,
+            ..Self::default()
+        })
+    }
+
+    /// how many rings of squares around the target are cleared
+    fn radius(&self) -> usize {
+        self.level as usize
+    }
+}
+
 impl Spell for JihadiWarrior {
     fn class_list(&self) -> String {
         "spell spell--jihadi-warrior".to_owned()
     }
 
     fn execute(&self, game: Game, square: Option<chess::Square>) -> Game {
-        // remove all pieces in a 3x3 square around the jihadi warrior
-        let mut board = game.board().clone();
+        // remove all pieces in a (2r+1)x(2r+1) square around the jihadi warrior
         let mut clear_squares = Vec::new();
-        let mut on = self.on;
-        match square {
-            Some(square) => on = Some(square),
-            None => (),
-        };
+        let on = square.or(self.on);
+        let r = self.radius();
         match on {
             Some(square) => {
-                for file in square.get_file().to_index() - 1..=square.get_file().to_index() + 1 {
-                    for rank in square.get_rank().to_index() - 1..=square.get_rank().to_index() + 1 {
-                        if file > 7 || rank > 7 {
-                            continue;
-                        }
+                let (file, rank) = (square.get_file().to_index(), square.get_rank().to_index());
+                for file in file.saturating_sub(r)..=std::cmp::min(file + r, 7) {
+                    for rank in rank.saturating_sub(r)..=std::cmp::min(rank + r, 7) {
                         let square = chess::Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file));
                         clear_squares.push(square);
                     }
                 }
-                for square in clear_squares {
-                    board = board.remove_piece(square);
-                }
-                let game = game.set_board(board);
-                let game = game.remove_spell(self.id);
-                return game;
+                game.clear_squares(&clear_squares)
             },
             None => {
                 info!("Jihadi Warrior not on board (yet! 👳🏾‍♂️)");
-                return game;
+                game
             },
         }
     }
@@ -86,4 +122,48 @@ impl Spell for JihadiWarrior {
     fn identifier(&self) -> u32 {
         self.id
     }
+
+    fn level(&self) -> u8 {
+        self.level
+    }
+
+    fn duration(&self) -> Option<u8> {
+        Some(self.valid_for as u8)
+    }
+
+    fn area(&self) -> Vec<(i32, i32)> {
+        let r = self.radius() as i32;
+        (-r..=r).flat_map(|file| (-r..=r).map(move |rank| (file, rank))).collect()
+    }
+
+    fn worth(&self) -> i32 {
+        150 * self.level as i32
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::to_value(State {
+            level: self.level,
+            on: self.on.map(square_name),
+            valid_for: self.valid_for,
+            valid: self.valid,
+        }).unwrap_or_default()
+    }
+
+    fn effect_class(&self) -> Option<&'static str> {
+        Some("effect effect--explosion")
+    }
+
+    fn arm(&self, square: chess::Square) -> Option<Arc<dyn Spell>> {
+        Some(Arc::new(Self {
+            on: Some(square),
+            ..self.clone()
+        }))
+    }
+
+    fn merge(&self, other: &dyn Spell) -> Option<Arc<dyn Spell>> {
+        if self.level >= MAX_LEVEL {
+            return None;
+        }
+        Some(Arc::new(Self::with_level(self.level + other.level())))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use chess::{Color, Square};
+
+use super::Spell;
+
+/// How fast a spell resolves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speed {
+    /// cast on your own turn, goes on the stack and can be responded to
+    Sorcery,
+    /// cast in response to an opponent's spell while it is on the stack
+    Instant,
+}
+
+/// A spell waiting on the stack to resolve
+#[derive(Clone, Debug)]
+pub struct Cast {
+    pub spell: Arc<dyn Spell>,
+    pub square: Option<Square>,
+    pub caster: Color,
+    /// pieces of this color are not affected when the cast resolves
+    pub shielded: Option<Color>,
+    /// ply at which an armed trap disarms itself
+    pub expires_at: Option<usize>,
+}
+
+// spelled out because deriving can't compare the `Arc<dyn Spell>`
+impl PartialEq for Cast {
+    fn eq(&self, other: &Self) -> bool {
+        *self.spell == *other.spell
+            && self.square == other.square
+            && self.caster == other.caster
+            && self.shielded == other.shielded
+            && self.expires_at == other.expires_at
+    }
+}
+
+impl Cast {
+    pub fn new(spell: Arc<dyn Spell>, square: Option<Square>, caster: Color) -> Self {
+        Self {
+            spell,
+            square,
+            caster,
+            shielded: None,
+            expires_at: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{CastError, Dispel, Game, Inventory, JihadiWarrior, Reflect, Shield};
+
+    /// white's warrior on e6 waiting for black, who holds `counter`
+    fn cast_against(counter: Arc<dyn Spell>) -> Game {
+        let warrior: Arc<dyn Spell> = Arc::new(JihadiWarrior::default());
+        let game = Game {
+            inventories: [Inventory::default().collect_spell(warrior.clone()), Inventory::default().collect_spell(counter)],
+            ..Game::new()
+        };
+        game.cast_spell(warrior, Square::E6).unwrap()
+    }
+
+    fn trap(game: &Game, color: Color) -> Option<&Cast> {
+        game.casted(color).values().next()
+    }
+
+    #[test]
+    fn unanswered_casts_resolve_as_cast() {
+        let game = cast_against(Arc::new(Shield)).resolve();
+        assert!(game.pending().is_none());
+        assert_eq!(game.traps(Color::White), vec![Square::E6]);
+    }
+
+    #[test]
+    fn shield_spares_the_responder() {
+        let game = cast_against(Arc::new(Shield)).respond(Arc::new(Shield)).unwrap();
+        assert_eq!(trap(&game, Color::White).and_then(|cast| cast.shielded), Some(Color::Black));
+        assert!(game.inventory(Color::Black).spells().is_empty());
+    }
+
+    #[test]
+    fn reflect_turns_the_cast_around() {
+        let game = cast_against(Arc::new(Reflect)).respond(Arc::new(Reflect)).unwrap();
+        assert!(game.traps(Color::White).is_empty());
+        assert_eq!(game.traps(Color::Black), vec![Square::E3]);
+    }
+
+    #[test]
+    fn dispel_cancels_the_cast() {
+        let game = cast_against(Arc::new(Dispel)).respond(Arc::new(Dispel)).unwrap();
+        assert!(game.pending().is_none());
+        assert!(game.traps(Color::White).is_empty() && game.traps(Color::Black).is_empty());
+    }
+
+    #[test]
+    fn casts_onto_pieces_go_off_at_once() {
+        let game = Game::new().with_inventories([Inventory::test(), Inventory::default()]);
+        let warrior = game.spells()[0].clone();
+        let game = game.cast_spell(warrior, Square::E7).unwrap().resolve();
+        assert!(trap(&game, Color::White).is_none());
+        assert_eq!(game.raw_board().piece_on(Square::D7), None);
+        // kings stay
+        assert!(game.raw_board().piece_on(Square::E8).is_some());
+    }
+
+    #[test]
+    fn only_held_spells_respond() {
+        let game = cast_against(Arc::new(Dispel));
+        assert_eq!(game.respond(Arc::new(Shield)).err(), Some(CastError::NotHeld));
+        assert!(game.pending().is_some());
+    }
+
+    #[test]
+    fn only_instants_respond() {
+        let game = cast_against(Arc::new(Shield));
+        assert_eq!(game.respond(Arc::new(JihadiWarrior::default())).err(), Some(CastError::Pending));
+    }
+}
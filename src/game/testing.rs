@@ -0,0 +1,35 @@
+//! Fixtures shared by the test modules
+use chess::Square;
+
+use super::board::parse_square;
+use super::moves::play;
+use super::{Game, Inventory};
+
+pub fn square(name: &str) -> Square {
+    parse_square(name).unwrap()
+}
+
+/// both sides hold a warrior: d4 picks up a spell, black arms a warrior on e5 and plays e6
+pub fn opening() -> Game {
+    let game = Game::new().with_inventories([Inventory::test(), Inventory::test()]);
+    let game = play(&game, square("d2"), square("d4")).unwrap();
+    let warrior = game.spells()[0].clone();
+    let game = game.cast_spell(warrior, square("e5")).unwrap();
+    play(&game.resolve(), square("e7"), square("e6")).unwrap()
+}
+
+/// `opening` with white's warrior cast on c6 and still on the stack
+pub fn midgame() -> Game {
+    let game = opening();
+    let warrior = game.spells()[0].clone();
+    game.cast_spell(warrior, square("c6")).unwrap()
+}
+
+/// a stop for searches that gives up after a few thousand nodes, the debug build is slow
+pub fn few_nodes() -> impl Fn() -> bool {
+    let asked = std::cell::Cell::new(0u32);
+    move || {
+        asked.set(asked.get() + 1);
+        asked.get() > 4
+    }
+}
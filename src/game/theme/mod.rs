@@ -0,0 +1,157 @@
+pub mod render;
+
+use std::collections::HashMap;
+
+use chess::{Color, Piece};
+use gloo::net::http::Request;
+use gloo::storage::{LocalStorage, Storage};
+use serde::Deserialize;
+use yew::platform::spawn_local;
+use yew::UseStateHandle;
+
+pub use render::*;
+
+/// where the list of installed themes lives, each one has a `theme.json` in `/assets/themes/{id}/`
+const INDEX: &str = "/assets/themes/index.json";
+const STORAGE_KEY: &str = "magic-chess.theme";
+
+/// A theme listed in the index
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Listing {
+    pub id: String,
+    pub name: String,
+}
+
+/// Square and label colors, any CSS color works
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Palette {
+    pub light: String,
+    pub dark: String,
+    pub label: String,
+}
+
+/// A theme manifest
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Theme {
+    #[serde(skip)]
+    pub id: String,
+    pub board: Palette,
+    /// path to a piece image with `{color}` and `{piece}` placeholders
+    pub pieces: String,
+    /// spell icon paths keyed by the spell's class modifier, spells left out keep their glyph
+    #[serde(default)]
+    pub spells: HashMap<String, String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            id: "classic".to_owned(),
+            board: Palette {
+                light: "white".to_owned(),
+                dark: "black".to_owned(),
+                label: "#009a5b".to_owned(),
+            },
+            pieces: "/assets/pieces/{color}/{piece}.svg".to_owned(),
+            spells: HashMap::new(),
+        }
+    }
+}
+
+impl Theme {
+    /// image of a piece in this theme's set
+    pub fn piece_src(&self, piece: Piece, color: Color) -> String {
+        let piece_color = match color {
+            Color::White => "white",
+            Color::Black => "black",
+        };
+        self.pieces
+            .replace("{color}", piece_color)
+            .replace("{piece}", &piece.to_string(color).to_lowercase())
+    }
+
+    /// CSS custom properties the stylesheet reads the palette and spell icons from
+    pub fn style(&self) -> String {
+        let mut style = format!(
+            "--square-light: {}; --square-dark: {}; --label: {};",
+            self.board.light, self.board.dark, self.board.label
+        );
+        for (spell, src) in &self.spells {
+            style.push_str(&format!(" --spell-icon-{}: url(\"{}\"); --spell-glyph-{}: \"\";", spell, src, spell));
+        }
+        style
+    }
+}
+
+/// the id of the theme picked last time, if any
+pub fn saved() -> Option<String> {
+    LocalStorage::get(STORAGE_KEY).ok()
+}
+
+/// fetches the list of installed themes into `themes`
+pub fn list(themes: UseStateHandle<Vec<Listing>>) {
+    spawn_local(async move {
+        let listing = match Request::get(INDEX).send().await {
+            Ok(response) => response.json::<Vec<Listing>>().await,
+            Err(e) => Err(e),
+        };
+        match listing {
+            Ok(listing) => themes.set(listing),
+            Err(e) => log::warn!("couldn't load the theme index: {}", e),
+        }
+    });
+}
+
+/// fetches the manifest of theme `id` into `theme` and remembers the choice
+pub fn load(id: String, theme: UseStateHandle<Theme>) {
+    spawn_local(async move {
+        let url = format!("/assets/themes/{}/theme.json", id);
+        let manifest = match Request::get(&url).send().await {
+            Ok(response) => response.json::<Theme>().await,
+            Err(e) => Err(e),
+        };
+        match manifest {
+            Ok(manifest) => {
+                let manifest = Theme { id: id.clone(), ..manifest };
+                if let Err(e) = LocalStorage::set(STORAGE_KEY, &id) {
+                    log::warn!("couldn't save the theme: {}", e);
+                }
+                theme.set(manifest)
+            },
+            Err(e) => log::warn!("couldn't load theme {}: {}", id, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forest() -> Theme {
+        serde_json::from_str(include_str!("../../../assets/themes/forest/theme.json")).unwrap()
+    }
+
+    #[test]
+    fn pieces_fill_in_color_and_kind() {
+        let theme = Theme { pieces: "/sets/{color}-{piece}.png".to_owned(), ..Theme::default() };
+        assert_eq!(theme.piece_src(Piece::Knight, Color::White), "/sets/white-n.png");
+        assert_eq!(theme.piece_src(Piece::Queen, Color::Black), "/sets/black-q.png");
+        assert_eq!(Theme::default().piece_src(Piece::King, Color::Black), "/assets/pieces/black/k.svg");
+    }
+
+    #[test]
+    fn style_carries_the_palette() {
+        let style = Theme::default().style();
+        assert_eq!(style, "--square-light: white; --square-dark: black; --label: #009a5b;");
+    }
+
+    #[test]
+    fn spell_icons_replace_their_glyphs() {
+        let theme = forest();
+        let style = theme.style();
+        assert!(style.starts_with("--square-light: #eeeed2; --square-dark: #769656;"));
+        assert!(style.contains("--spell-icon-shield: url(\"/assets/themes/forest/spells/shield.svg\"); --spell-glyph-shield: \"\";"));
+        assert_eq!(style.matches("--spell-glyph-").count(), theme.spells.len());
+        assert!(!Theme::default().style().contains("--spell-icon-"));
+    }
+}
@@ -0,0 +1,27 @@
+use yew::prelude::*;
+use web_sys::HtmlSelectElement;
+use super::{load, Listing, Theme};
+
+/// dropdown of the installed themes
+pub fn render(theme: &UseStateHandle<Theme>, themes: &UseStateHandle<Vec<Listing>>) -> Html {
+    if themes.is_empty() {
+        return html! {};
+    }
+    let onchange = {
+        let theme = theme.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            load(select.value(), theme.clone())
+        })
+    };
+    html! {
+        <label class="theme--picker p-4 shadow-xl rounded-lg text-sm">
+            {"Theme "}
+            <select {onchange}>
+                { themes.iter().map(|listing| html! {
+                    <option value={listing.id.clone()} selected={listing.id == theme.id}>{listing.name.clone()}</option>
+                }).collect::<Html>() }
+            </select>
+        </label>
+    }
+}
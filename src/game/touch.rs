@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use chess::Square;
+use yew::prelude::*;
+
+use super::{moves, Game, Spell};
+
+/// What is being dragged by a finger or pen
+#[derive(Clone, Debug)]
+pub enum Payload {
+    Piece { from: Square, src: String },
+    Spell(Arc<dyn Spell>),
+}
+
+/// A pointer drag in progress, HTML5 drag events don't fire for touch on most mobile browsers
+#[derive(Clone, Debug)]
+pub struct Drag {
+    payload: Payload,
+    point: (f64, f64),
+}
+
+impl Drag {
+    /// the square of the dragged piece, if a piece is being dragged
+    pub fn piece_from(&self) -> Option<Square> {
+        match self.payload {
+            Payload::Piece { from, .. } => Some(from),
+            Payload::Spell(_) => None,
+        }
+    }
+}
+
+fn point(e: &PointerEvent) -> (f64, f64) {
+    (e.client_x() as f64, e.client_y() as f64)
+}
+
+/// starts a pointer drag, mouse input is left to the native drag and drop
+pub fn start(drag: &UseStateHandle<Option<Drag>>, payload: Payload) -> Callback<PointerEvent> {
+    let drag = drag.clone();
+    Callback::from(move |e: PointerEvent| {
+        if e.pointer_type() == "mouse" {
+            return;
+        }
+        e.prevent_default();
+        drag.set(Some(Drag { payload: payload.clone(), point: point(&e) }));
+    })
+}
+
+pub fn follow(drag: &UseStateHandle<Option<Drag>>) -> Callback<PointerEvent> {
+    let drag = drag.clone();
+    Callback::from(move |e: PointerEvent| {
+        if let Some(current) = drag.as_ref() {
+            drag.set(Some(Drag { point: point(&e), ..current.clone() }));
+        }
+    })
+}
+
+/// drops the dragged piece or spell onto the square under the pointer
+pub fn end(drag: &UseStateHandle<Option<Drag>>, state: &UseStateHandle<Game>) -> Callback<PointerEvent> {
+    let drag = drag.clone();
+    let state = state.clone();
+    Callback::from(move |e: PointerEvent| {
+        let current = match drag.as_ref() {
+            Some(current) => current.clone(),
+            None => return,
+        };
+        drag.set(None);
+        let game = match current.payload {
+            Payload::Piece { from, .. } => moves::drop_piece(&state, from, point(&e)),
+            Payload::Spell(spell) => moves::drop_spell(&state, spell, point(&e)),
+        };
+        state.set(game)
+    })
+}
+
+pub fn cancel(drag: &UseStateHandle<Option<Drag>>) -> Callback<PointerEvent> {
+    let drag = drag.clone();
+    Callback::from(move |_| drag.set(None))
+}
+
+/// the floating image following the pointer
+pub fn render(drag: &UseStateHandle<Option<Drag>>) -> Html {
+    let current = match drag.as_ref() {
+        Some(current) => current,
+        None => return html! {},
+    };
+    let style = format!("left: {}px; top: {}px;", current.point.0, current.point.1);
+    match &current.payload {
+        Payload::Piece { src, .. } => html! {
+            <div class="ghost" {style}>
+                <img src={src.clone()} />
+            </div>
+        },
+        Payload::Spell(spell) => html! {
+            <div class="ghost spell--icon" {style}>
+                <div class={spell.class_list()}>
+                </div>
+            </div>
+        },
+    }
+}
@@ -0,0 +1,166 @@
+//! A client for the Universal Chess Interface, so any UCI engine can take over while no spells
+//! are in play. Writing commands and reading replies works anywhere; talking to an engine
+//! process over stdin/stdout needs a native build. `choose` and `hint` put an engine behind the
+//! computer opponent and hints for a native front end embedding the game. The web app can't
+//! start processes, so it doesn't use them and always searches itself.
+#[cfg(not(target_arch = "wasm32"))]
+mod process;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use process::*;
+
+use std::str::FromStr;
+
+use chess::ChessMove;
+
+use super::{Game, Kind};
+
+/// Error Type for an engine that can't be started or doesn't speak UCI
+#[derive(Clone, Debug, PartialEq)]
+pub enum UciError {
+    Spawn(String),
+    Io(String),
+    Closed,
+    /// no reply in time, the engine may have hung
+    Timeout,
+    Protocol(String),
+}
+
+impl std::fmt::Display for UciError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UciError::Spawn(e) => write!(f, "Can't start the engine: {}", e),
+            UciError::Io(e) => write!(f, "Can't talk to the engine: {}", e),
+            UciError::Closed => write!(f, "The engine quit"),
+            UciError::Timeout => write!(f, "The engine didn't answer in time"),
+            UciError::Protocol(line) => write!(f, "Unexpected reply {}", line),
+        }
+    }
+}
+
+/// How long the engine may think
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    Depth(u8),
+    /// milliseconds
+    MoveTime(u64),
+}
+
+/// An engine's opinion of a position, for the side to move
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Score {
+    Centipawns(i32),
+    /// moves until mate, negative when getting mated
+    Mate(i32),
+}
+
+/// What the engine settled on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Analysis {
+    /// `None` when the side to move has no legal moves
+    pub best: Option<ChessMove>,
+    /// from the deepest `info` line that had one
+    pub score: Option<Score>,
+    pub depth: u8,
+}
+
+/// the `position` command for the chess part of `game`
+pub fn position(game: &Game) -> String {
+    format!("position fen {}", game.raw_board())
+}
+
+/// the `go` command for `limit`
+pub fn go(limit: Limit) -> String {
+    match limit {
+        Limit::Depth(depth) => format!("go depth {}", depth),
+        Limit::MoveTime(ms) => format!("go movetime {}", ms),
+    }
+}
+
+/// the depth and score of an `info` line, when it has both
+pub fn parse_info(line: &str) -> Option<(u8, Score)> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    if words.first() != Some(&"info") {
+        return None;
+    }
+    let after = |key: &str| words.iter().position(|word| *word == key).and_then(|i| words.get(i + 1));
+    let depth = after("depth")?.parse().ok()?;
+    let at = words.iter().position(|word| *word == "score")?;
+    let value = words.get(at + 2)?.parse().ok()?;
+    let score = match *words.get(at + 1)? {
+        "cp" => Score::Centipawns(value),
+        "mate" => Score::Mate(value),
+        _ => return None,
+    };
+    Some((depth, score))
+}
+
+/// the move of a `bestmove` line, `Some(None)` for `bestmove (none)`, `None` for other lines
+pub fn parse_bestmove(line: &str) -> Option<Result<Option<ChessMove>, UciError>> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("bestmove") {
+        return None;
+    }
+    Some(match words.next() {
+        Some("(none)") | Some("0000") => Ok(None),
+        Some(m) => ChessMove::from_str(m).map(Some).map_err(|_| UciError::Protocol(line.to_owned())),
+        None => Err(UciError::Protocol(line.to_owned())),
+    })
+}
+
+/// the analysis a search's output adds up to, once it reached its `bestmove` line
+pub fn parse_search<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<Result<Analysis, UciError>> {
+    let mut analysis = Analysis { best: None, score: None, depth: 0 };
+    for line in lines {
+        if let Some((depth, score)) = parse_info(line) {
+            if depth >= analysis.depth {
+                analysis.depth = depth;
+                analysis.score = Some(score);
+            }
+        }
+        if let Some(best) = parse_bestmove(line) {
+            return Some(best.map(|best| Analysis { best, ..analysis }));
+        }
+    }
+    None
+}
+
+impl Game {
+    /// whether this is plain chess by now: no spells on the board, in hand, armed or on the stack
+    pub fn is_plain_chess(&self) -> bool {
+        self.board.spells().iter().all(|kind| matches!(kind, Kind::None))
+            && self.inventories.iter().all(|inventory| inventory.spells().is_empty())
+            && self.casted_mine.is_empty()
+            && self.casted_other.is_empty()
+            && self.stack.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_search() {
+        let output = [
+            "info depth 1 seldepth 1 score cp 30 nodes 20 pv e2e4",
+            "info depth 2 score mate 3 pv d1h5",
+            "info string done",
+            "bestmove d1h5 ponder g7g6",
+        ];
+        let analysis = parse_search(output).unwrap().unwrap();
+        assert_eq!(analysis.best.map(|m| m.to_string()), Some("d1h5".to_owned()));
+        assert_eq!(analysis.score, Some(Score::Mate(3)));
+        assert_eq!(analysis.depth, 2);
+        assert_eq!(parse_search(["bestmove (none)"]).unwrap().unwrap().best, None);
+        assert!(parse_search(["info depth 1 score cp 3"]).is_none());
+    }
+
+    #[test]
+    fn only_plain_positions_are_plain_chess() {
+        let plain = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert!(plain.is_plain_chess());
+        assert_eq!(position(&plain), "position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert!(!Game::new().is_plain_chess());
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use super::{go, parse_search, position, Analysis, Limit, UciError};
+use crate::game::ai::{self, Action, Computer, Hint, HintError, HINT_MS};
+use crate::game::Game;
+
+/// how long an engine gets to exit after `quit` before it is killed
+const QUIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// how long an engine gets to answer anything but a search
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// how long a search limited by depth may take, and how far past its time a timed one may run
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(30);
+const SEARCH_GRACE: Duration = Duration::from_secs(1);
+
+/// A UCI engine, usually a child process talking over stdin/stdout
+pub struct Engine {
+    child: Option<Child>,
+    input: Box<dyn Write + Send>,
+    /// the engine's output line by line, read on a thread of its own so waiting can time out
+    lines: Receiver<std::io::Result<String>>,
+    /// what the engine calls itself in its `id name` line
+    name: String,
+}
+
+impl Engine {
+    /// starts the engine at `path` and waits until it's ready for a position
+    pub fn spawn(path: &str) -> Result<Self, UciError> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| UciError::Spawn(e.to_string()))?;
+        let input = child.stdin.take().ok_or(UciError::Closed)?;
+        let output = BufReader::new(child.stdout.take().ok_or(UciError::Closed)?);
+        Self::start(Some(child), Box::new(input), output, path)
+    }
+
+    /// talks UCI to an engine already running elsewhere, writing commands to `input` and reading
+    /// replies from `output`, and waits until it's ready for a position
+    pub fn connect(input: impl Write + Send + 'static, output: impl BufRead + Send + 'static) -> Result<Self, UciError> {
+        Self::start(None, Box::new(input), output, "UCI engine")
+    }
+
+    fn start(child: Option<Child>, input: Box<dyn Write + Send>, output: impl BufRead + Send + 'static, fallback: &str) -> Result<Self, UciError> {
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in output.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = Self { child, input, lines, name: String::new() };
+        engine.send("uci")?;
+        let lines = engine.read_until(|line| line == "uciok", REPLY_TIMEOUT)?;
+        engine.name = lines.iter()
+            .find_map(|line| line.strip_prefix("id name "))
+            .unwrap_or(fallback)
+            .to_owned();
+        engine.sync()?;
+        Ok(engine)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), UciError> {
+        writeln!(self.input, "{}", command).and_then(|_| self.input.flush()).map_err(|e| UciError::Io(e.to_string()))
+    }
+
+    /// the engine's output up to and including the first line `done` accepts, unless that takes
+    /// longer than `timeout`
+    fn read_until(&mut self, done: impl Fn(&str) -> bool, timeout: Duration) -> Result<Vec<String>, UciError> {
+        let deadline = Instant::now() + timeout;
+        let mut lines = Vec::new();
+        loop {
+            let line = match self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(Ok(line)) => line,
+                Ok(Err(e)) => return Err(UciError::Io(e.to_string())),
+                Err(RecvTimeoutError::Timeout) => return Err(UciError::Timeout),
+                Err(RecvTimeoutError::Disconnected) => return Err(UciError::Closed),
+            };
+            let line = line.trim().to_owned();
+            let finished = done(&line);
+            lines.push(line);
+            if finished {
+                return Ok(lines);
+            }
+        }
+    }
+
+    /// waits until the engine is ready, skipping whatever is left of a search that timed out
+    fn sync(&mut self) -> Result<(), UciError> {
+        self.send("isready")?;
+        self.read_until(|line| line == "readyok", REPLY_TIMEOUT).map(drop)
+    }
+
+    /// searches the chess part of `game`, ignoring any spells
+    pub fn analyse(&mut self, game: &Game, limit: Limit) -> Result<Analysis, UciError> {
+        self.sync()?;
+        self.send(&position(game))?;
+        self.send(&go(limit))?;
+        let timeout = match limit {
+            Limit::Depth(_) => SEARCH_TIMEOUT,
+            Limit::MoveTime(ms) => Duration::from_millis(ms) + SEARCH_GRACE,
+        };
+        let lines = self.read_until(|line| line.starts_with("bestmove"), timeout).inspect_err(|_| {
+            let _ = self.send("stop");
+        })?;
+        parse_search(lines.iter().map(String::as_str)).unwrap_or_else(|| Err(UciError::Protocol(lines.join("\n"))))
+    }
+
+    /// the engine's move for `game`, `None` while spells are in play and it has nothing to say
+    pub fn action(&mut self, game: &Game, limit: Limit) -> Result<Option<Action>, UciError> {
+        if !game.is_plain_chess() {
+            return Ok(None);
+        }
+        Ok(self.analyse(game, limit)?.best.map(Action::Move))
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let child = match self.child.as_mut() {
+            Some(child) => child,
+            None => return,
+        };
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        let mut exited = || child.try_wait().ok().flatten().is_some();
+        while !exited() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        if !exited() {
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+    }
+}
+
+/// the engine's action for `game`, or the built-in search's while spells are in play or if the
+/// engine fails
+fn action_or(engine: &mut Engine, game: &Game, limit: Limit, search: impl FnOnce() -> Option<Action>) -> Option<Action> {
+    match engine.action(game, limit) {
+        Ok(Some(action)) => Some(action),
+        Ok(None) => search(),
+        Err(e) => {
+            log::warn!("{}: {}", engine.name(), e);
+            search()
+        },
+    }
+}
+
+/// what `computer` plays with `engine` as its backend: the engine's move in plain chess, the
+/// built-in search while spells are in play or if the engine fails
+pub fn choose(engine: &mut Engine, game: &Game, computer: &Computer, seed: u64, stop: &dyn Fn() -> bool) -> Option<Action> {
+    let limit = Limit::MoveTime(computer.level.time_ms() as u64);
+    action_or(engine, game, limit, || ai::choose(game, computer.level, computer.personality, seed, stop))
+}
+
+/// a hint from `engine` in plain chess, from the built-in search otherwise, see `Game::hint`
+pub fn hint(engine: &mut Engine, game: &Game, stop: &dyn Fn() -> bool) -> Result<(Game, Hint), HintError> {
+    game.hint_from(|seen| {
+        action_or(engine, seen, Limit::MoveTime(HINT_MS as u64), || ai::suggest(seen, stop))
+    })
+}
@@ -0,0 +1,101 @@
+//! Drives a stand-in engine through the UCI client. The stand-in runs on a thread and talks over
+//! pipes: it answers the handshake and always plays the first legal move, reporting a fixed
+//! score. With `answers` off it never finishes a search.
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use chess::{Board, MoveGen};
+use trunk_template::game::ai::{Action, Computer};
+use trunk_template::game::uci::{choose, hint, Engine, Limit, Score, UciError};
+use trunk_template::game::Game;
+
+fn mock(commands: impl BufRead, mut replies: impl Write, answers: bool) {
+    let mut board = Board::default();
+    for line in commands.lines().map_while(Result::ok) {
+        let reply = match line.split_whitespace().next() {
+            Some("uci") => "id name Mock\nid author magic-chess\nuciok".to_owned(),
+            Some("isready") => "readyok".to_owned(),
+            Some("position") => {
+                board = match line.split_once(" fen ") {
+                    Some((_, fen)) => Board::from_str(fen).unwrap_or_default(),
+                    None => Board::default(),
+                };
+                continue;
+            },
+            Some("go") if !answers => continue,
+            Some("go") => match MoveGen::new_legal(&board).next() {
+                Some(m) => format!("info depth 1 score cp 12 pv {}\nbestmove {}", m, m),
+                None => "bestmove (none)".to_owned(),
+            },
+            Some("quit") => break,
+            _ => continue,
+        };
+        let _ = writeln!(replies, "{}", reply);
+        let _ = replies.flush();
+    }
+}
+
+fn connect(answers: bool) -> Engine {
+    let (commands, input) = std::io::pipe().unwrap();
+    let (output, replies) = std::io::pipe().unwrap();
+    std::thread::spawn(move || mock(BufReader::new(commands), replies, answers));
+    Engine::connect(input, BufReader::new(output)).unwrap()
+}
+
+fn engine() -> Engine {
+    connect(true)
+}
+
+#[test]
+fn handshake() {
+    assert_eq!(engine().name(), "Mock");
+}
+
+#[test]
+fn analyses_plain_chess() {
+    let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    let analysis = engine().analyse(&game, Limit::Depth(1)).unwrap();
+    assert!(analysis.best.is_some_and(|m| game.raw_board().legal(m)));
+    assert_eq!(analysis.score, Some(Score::Centipawns(12)));
+}
+
+#[test]
+fn reports_positions_without_moves() {
+    let mated = Game::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+    assert_eq!(engine().analyse(&mated, Limit::MoveTime(10)).unwrap().best, None);
+}
+
+#[test]
+fn leaves_spells_to_the_built_in_search() {
+    let mut engine = engine();
+    let magic = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 W@e4 -/- - 0 -").unwrap();
+    assert_eq!(engine.action(&magic, Limit::Depth(1)).unwrap(), None);
+    let computer = Computer::new(chess::Color::White);
+    let action = choose(&mut engine, &magic, &computer, 0, &|| true);
+    assert!(matches!(action, Some(Action::Move(_))));
+}
+
+#[test]
+fn hints_in_plain_chess() {
+    let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    let first = chess::MoveGen::new_legal(game.raw_board()).next().unwrap();
+    // the built-in search would give up at once, so the hint is the engine's
+    let (next, hint) = hint(&mut engine(), &game, &|| true).unwrap();
+    assert_eq!(hint.action, Action::Move(first));
+    assert_eq!(next.hints_left(chess::Color::White), game.hints_left(chess::Color::White) - 1);
+}
+
+#[test]
+fn gives_up_on_engines_that_hang() {
+    let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    let started = Instant::now();
+    assert_eq!(connect(false).analyse(&game, Limit::MoveTime(10)), Err(UciError::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(3));
+}
+
+#[test]
+fn reports_engines_that_cant_start() {
+    assert!(matches!(Engine::spawn("/nonexistent/engine"), Err(UciError::Spawn(_))));
+}
+